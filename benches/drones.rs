@@ -0,0 +1,212 @@
+// Drones running on their own threads and wired with channels, as `Network` does:
+// fragment throughput through chains, flood cost on full meshes, forwarding latency under PDR.
+// `cargo bench --bench drones`, reports end up in target/criterion.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::{DroneBuilder, DroneConfig};
+
+const CLIENT: NodeId = 200;
+const SERVER: NodeId = 201;
+const BATCH: u64 = 1000;
+
+struct Topology {
+    inboxes: HashMap<NodeId, Sender<Packet>>,
+    client: Receiver<Packet>,
+    server: Receiver<Packet>,
+    events: Receiver<DroneEvent>,
+    _commands: Vec<Sender<DroneCommand>>, // The drones stop when they're dropped
+}
+
+// `links` between drones, the client is linked to `first` and the server to `last`.
+fn spawn(
+    drones: &[NodeId],
+    links: &[(NodeId, NodeId)],
+    first: NodeId,
+    last: NodeId,
+    pdr: f32,
+) -> Topology {
+    let (controller_send, events) = unbounded();
+    let mut inboxes = HashMap::new();
+    let mut packet_recv = HashMap::new();
+    for id in drones.iter().copied().chain([CLIENT, SERVER]) {
+        let (tx, rx) = unbounded();
+        inboxes.insert(id, tx);
+        packet_recv.insert(id, rx);
+    }
+    let mut neighbours: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    for (a, b) in links
+        .iter()
+        .copied()
+        .chain([(CLIENT, first), (last, SERVER)])
+    {
+        neighbours.entry(a).or_default().push(b);
+        neighbours.entry(b).or_default().push(a);
+    }
+    let mut commands = Vec::new();
+    for id in drones {
+        let (command_send, command_recv) = unbounded();
+        let mut drone = DroneBuilder::new(*id)
+            .config(DroneConfig {
+                logging: false,
+                seed: Some(7), // Same drops on every run
+                flood_history_limit: Some(64),
+                ..DroneConfig::default()
+            })
+            .pdr(pdr)
+            .controller(controller_send.clone(), command_recv)
+            .packet_recv(packet_recv.remove(id).unwrap())
+            .neighbours(
+                neighbours[id]
+                    .iter()
+                    .map(|n| (*n, inboxes[n].clone()))
+                    .collect(),
+            )
+            .build()
+            .unwrap();
+        thread::spawn(move || drone.run());
+        commands.push(command_send);
+    }
+    Topology {
+        client: packet_recv.remove(&CLIENT).unwrap(),
+        server: packet_recv.remove(&SERVER).unwrap(),
+        inboxes,
+        events,
+        _commands: commands,
+    }
+}
+
+fn chain(length: u8, pdr: f32) -> (Topology, Vec<NodeId>) {
+    let drones: Vec<NodeId> = (1..=length).collect();
+    let links: Vec<(NodeId, NodeId)> = drones.windows(2).map(|w| (w[0], w[1])).collect();
+    let topology = spawn(&drones, &links, 1, length, pdr);
+    let route = std::iter::once(CLIENT)
+        .chain(drones)
+        .chain([SERVER])
+        .collect();
+    (topology, route)
+}
+
+fn mesh(size: u8) -> Topology {
+    let drones: Vec<NodeId> = (1..=size).collect();
+    let mut links = Vec::new();
+    for a in 1..=size {
+        for b in a + 1..=size {
+            links.push((a, b));
+        }
+    }
+    spawn(&drones, &links, 1, size, 0.0)
+}
+
+fn fragment(route: &[NodeId], index: u64) -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader {
+            hop_index: 1,
+            hops: route.to_vec(),
+        },
+        session_id: index,
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: index,
+            total_n_fragments: BATCH,
+            length: 128,
+            data: [0xAB; FRAGMENT_DSIZE],
+        }),
+    }
+}
+
+fn chains(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chain_fragments");
+    group.throughput(Throughput::Elements(BATCH));
+    group.sample_size(20);
+    for length in [1, 5, 10, 50] {
+        let (topology, route) = chain(length, 0.0);
+        group.bench_with_input(BenchmarkId::from_parameter(length), &route, |b, route| {
+            b.iter(|| {
+                for index in 0..BATCH {
+                    topology.inboxes[&route[1]]
+                        .send(fragment(route, index))
+                        .unwrap();
+                }
+                for _ in 0..BATCH {
+                    topology.server.recv().unwrap();
+                }
+                topology.events.try_iter().for_each(drop);
+            })
+        });
+    }
+    group.finish();
+}
+
+// A FloodRequest from the client reaches every drone of a full mesh of `size`, each duplicate
+// is answered: (size - 1) * (size - 2) FloodResponses come back.
+fn floods(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mesh_flood");
+    group.sample_size(20);
+    for size in [5u8, 10, 20] {
+        let topology = mesh(size);
+        let responses = (size as usize - 1) * (size as usize - 2);
+        let mut flood_id = 0;
+        group.throughput(Throughput::Elements(responses as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                flood_id += 1;
+                let request = Packet {
+                    routing_header: SourceRoutingHeader {
+                        hop_index: 0,
+                        hops: vec![],
+                    },
+                    session_id: flood_id,
+                    pack_type: PacketType::FloodRequest(FloodRequest {
+                        flood_id,
+                        initiator_id: CLIENT,
+                        path_trace: vec![(CLIENT, NodeType::Client)],
+                    }),
+                };
+                topology.inboxes[&1].send(request).unwrap();
+                for _ in 0..responses {
+                    topology.client.recv().unwrap();
+                }
+                topology.events.try_iter().for_each(drop);
+            })
+        });
+    }
+    group.finish();
+}
+
+// Time from the client sending a fragment through 5 drones to its outcome: the server
+// receiving it, or the client receiving the Nack of a drop.
+fn latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chain5_latency");
+    for pdr in [0.0, 0.1, 0.3] {
+        let (topology, route) = chain(5, pdr);
+        group.bench_with_input(BenchmarkId::new("pdr", pdr), &route, |b, route| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for index in 0..iters {
+                    let start = Instant::now();
+                    topology.inboxes[&route[1]]
+                        .send(fragment(route, index))
+                        .unwrap();
+                    crossbeam_channel::select! {
+                        recv(topology.server) -> _ => {}
+                        recv(topology.client) -> _ => {}
+                    }
+                    total += start.elapsed();
+                }
+                topology.events.try_iter().for_each(drop);
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, chains, floods, latency);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+// Forwarding throughput of a single drone on 128-byte fragments and on floods.
+// The packets are queued before the drone runs, which returns once it handled them all.
+// Compare two revisions with `cargo bench --bench forwarding -- --save-baseline before`
+// on the first one and `-- --baseline before` on the second.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{drone_with, flood_request, fragment, packet, quiet, Ends};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use wg_2024::drone::Drone as _;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+use LeDron_James::Drone;
+
+const BATCH: u64 = 1000;
+
+// Drone 1 with `batch` queued, received from 0, and `fan_out` more neighbours: 2 to 2 + fan_out - 1.
+fn drone(fan_out: u8, batch: impl Iterator<Item = Packet>) -> (Drone, Ends) {
+    let neighbours: Vec<NodeId> = std::iter::once(0).chain(2..2 + fan_out).collect();
+    drone_with(1, &neighbours, quiet(), 0.0).queue(batch)
+}
+
+fn fragments() -> impl Iterator<Item = Packet> {
+    (0..BATCH).map(|index| packet(vec![10, 0, 1, 2, 20], 2, fragment(index, BATCH)))
+}
+
+fn floods() -> impl Iterator<Item = Packet> {
+    (0..BATCH).map(|flood_id| flood_request(flood_id, 10, &[10, 0]))
+}
+
+fn forwarding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("forwarding");
+    group.throughput(Throughput::Elements(BATCH));
+    group.bench_function("fragments_128b", |b| {
+        b.iter_batched(
+            || drone(1, fragments()),
+            |(mut drone, ends)| {
+                drone.run();
+                ends
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("floods_fan_out_8", |b| {
+        b.iter_batched(
+            || drone(8, floods()),
+            |(mut drone, ends)| {
+                drone.run();
+                ends
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, forwarding);
+criterion_main!(benches);
@@ -0,0 +1,170 @@
+#![no_main]
+// Arbitrary packets, headers and flood traces included, handled by a drone with mock
+// neighbours: whatever comes from the network, the drone mustn't panic.
+// `cargo +nightly fuzz run packet_handling` from the repository root.
+
+use arbitrary::Arbitrary;
+use crossbeam_channel::unbounded;
+use libfuzzer_sys::fuzz_target;
+use wg_2024::controller::DroneCommand;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::*;
+use LeDron_James::builder::{
+    CrashedFloodPolicy, DroneBuilder, DroneConfig, FloodDedup, Scheduling,
+};
+
+// Drone 1, linked to these. Ids are small so that random routes sometimes make sense.
+const NEIGHBOURS: [NodeId; 3] = [0, 2, 3];
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    pdr: u8, // Over 255
+    crashed: bool,
+    respond_when_crashed: bool,
+    priority: bool,
+    paths: u8, // FloodDedup::Paths when not 0
+    packets: Vec<FuzzPacket>,
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzPacket {
+    hop_index: u8,
+    hops: Vec<u8>,
+    session_id: u64,
+    kind: Kind,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Kind {
+    Fragment {
+        index: u64,
+        total: u64,
+        length: u8,
+    },
+    Ack(u64),
+    Nack {
+        index: u64,
+        nack: u8,
+        node: NodeId,
+    },
+    FloodRequest {
+        flood_id: u64,
+        initiator: NodeId,
+        trace: Vec<(NodeId, u8)>,
+    },
+    FloodResponse {
+        flood_id: u64,
+        trace: Vec<(NodeId, u8)>,
+    },
+}
+
+fn node_type(kind: u8) -> NodeType {
+    match kind % 3 {
+        0 => NodeType::Client,
+        1 => NodeType::Drone,
+        _ => NodeType::Server,
+    }
+}
+
+impl From<FuzzPacket> for Packet {
+    fn from(fuzz: FuzzPacket) -> Self {
+        let trace = |trace: Vec<(NodeId, u8)>| -> Vec<(NodeId, NodeType)> {
+            trace
+                .into_iter()
+                .map(|(id, kind)| (id, node_type(kind)))
+                .collect()
+        };
+        let pack_type = match fuzz.kind {
+            Kind::Fragment {
+                index,
+                total,
+                length,
+            } => PacketType::MsgFragment(Fragment {
+                fragment_index: index,
+                total_n_fragments: total,
+                length,
+                data: [length; FRAGMENT_DSIZE],
+            }),
+            Kind::Ack(index) => PacketType::Ack(Ack {
+                fragment_index: index,
+            }),
+            Kind::Nack { index, nack, node } => PacketType::Nack(Nack {
+                fragment_index: index,
+                nack_type: match nack % 4 {
+                    0 => NackType::ErrorInRouting(node),
+                    1 => NackType::DestinationIsDrone,
+                    2 => NackType::Dropped,
+                    _ => NackType::UnexpectedRecipient(node),
+                },
+            }),
+            Kind::FloodRequest {
+                flood_id,
+                initiator,
+                trace: path,
+            } => PacketType::FloodRequest(FloodRequest {
+                flood_id,
+                initiator_id: initiator,
+                path_trace: trace(path),
+            }),
+            Kind::FloodResponse {
+                flood_id,
+                trace: path,
+            } => PacketType::FloodResponse(FloodResponse {
+                flood_id,
+                path_trace: trace(path),
+            }),
+        };
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index: fuzz.hop_index as usize,
+                hops: fuzz.hops.into_iter().map(|hop| hop % 8).collect(),
+            },
+            session_id: fuzz.session_id,
+            pack_type,
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let (controller_send, _events) = unbounded();
+    let (command_send, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let mut builder = DroneBuilder::new(1)
+        .config(DroneConfig {
+            logging: false,
+            seed: Some(0),
+            crashed_floods: match input.respond_when_crashed {
+                true => CrashedFloodPolicy::Respond,
+                false => CrashedFloodPolicy::Drop,
+            },
+            scheduling: match input.priority {
+                true => Scheduling::Priority,
+                false => Scheduling::Fifo,
+            },
+            flood_dedup: match input.paths {
+                0 => FloodDedup::Wgl,
+                paths => FloodDedup::Paths(paths as usize),
+            },
+            ..DroneConfig::default()
+        })
+        .pdr(input.pdr as f32 / 255.0)
+        .controller(controller_send, command_recv)
+        .packet_recv(packet_recv);
+    let mut neighbours = Vec::new();
+    for id in NEIGHBOURS {
+        let (tx, rx) = unbounded();
+        builder = builder.neighbour(id, tx);
+        neighbours.push(rx);
+    }
+    let mut drone = builder.build().unwrap();
+    if input.crashed {
+        command_send.send(DroneCommand::Crash).unwrap();
+    }
+    for packet in input.packets {
+        packet_send.send(packet.into()).unwrap();
+    }
+    // The drone returns once it handled every packet, on this thread so a panic is seen
+    drop(packet_send);
+    drone.run();
+});
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::net::UdpSocket;
+use std::process::{Child, Command};
+use std::sync::Arc;
+use std::{env, process, thread};
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::{DroneBuilder, DroneConfig};
+use LeDron_James::codec::*;
+use LeDron_James::config::NetworkConfig;
+use LeDron_James::transport::UdpTransport;
+
+const USAGE: &str = "usage:
+  ledron-node <topology.json> <drone-id>   run a single drone on 127.0.0.1:(base_port + id)
+  ledron-node <topology.json> launch       spawn every drone as its own process and act as controller
+
+launcher commands (stdin):
+  kill <id>                  SIGKILL the drone process
+  crash <id>                 send DroneCommand::Crash
+  pdr <id> <value>           send DroneCommand::SetPacketDropRate
+  add <id> <neighbour>       send DroneCommand::AddSender
+  remove <id> <neighbour>    send DroneCommand::RemoveSender
+  flood <client-id> <flood-id>
+  quit";
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let config = NetworkConfig::from_file(&args[1]).unwrap_or_else(|er| {
+        eprintln!("{}", er);
+        process::exit(1);
+    });
+    if args[2] == "launch" {
+        launch(&args[1], &config);
+    } else {
+        match args[2].parse::<NodeId>() {
+            Ok(id) => run_drone(&config, id),
+            Err(_) => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+}
+
+fn run_drone(config: &NetworkConfig, id: NodeId) {
+    let Some(pdr) = config.pdr_of(id) else {
+        eprintln!("Drone {} is not in the topology", id);
+        process::exit(1);
+    };
+    let neighbours = config
+        .neighbours_of(id)
+        .into_iter()
+        .map(|n| (n, config.address_of(n)))
+        .collect();
+    let channels = UdpTransport::bind(id, config.address_of(id))
+        .and_then(|transport| transport.wire(neighbours, Some(config.controller_address())))
+        .unwrap_or_else(|er| {
+            eprintln!("Drone {}: {}", id, er);
+            process::exit(1);
+        });
+    // LEDRON_* variables tune the drone, see DroneConfig::from_env
+    let mut drone = DroneConfig::from_env()
+        .and_then(|drone_config| {
+            DroneBuilder::new(id)
+                .config(drone_config)
+                .controller(channels.controller_send, channels.controller_recv)
+                .packet_recv(channels.packet_recv)
+                .neighbours(channels.packet_send)
+                .pdr(pdr)
+                .build()
+        })
+        .unwrap_or_else(|er| {
+            eprintln!("Drone {}: {}", id, er);
+            process::exit(1);
+        });
+    drone.run();
+}
+
+fn launch(topology: &str, config: &NetworkConfig) {
+    let exe = env::current_exe().expect("Can't locate own executable");
+    let mut children: HashMap<NodeId, Child> = HashMap::new();
+    for d in &config.drone {
+        match Command::new(&exe)
+            .arg(topology)
+            .arg(d.id.to_string())
+            .spawn()
+        {
+            Ok(child) => {
+                println!("Drone {} -> pid {}", d.id, child.id());
+                children.insert(d.id, child);
+            }
+            Err(er) => eprintln!("Drone {}: failed to spawn [{}]", d.id, er),
+        }
+    }
+
+    // Controller socket: we print every event the drones report.
+    let controller = Arc::new(
+        UdpSocket::bind(config.controller_address()).expect("Can't bind controller address"),
+    );
+    let listener = controller.clone();
+    thread::spawn(move || {
+        let mut buf = vec![0u8; 65507];
+        while let Ok((len, _)) = listener.recv_from(&mut buf) {
+            if let Ok(Frame::Event(id, event)) = decode_frame(&buf[..len]) {
+                println!("[controller] drone {}: {:?}", id, event);
+            }
+        }
+    });
+
+    // Clients and servers are plain sockets here, we print whatever reaches them.
+    let mut edges: HashMap<NodeId, Arc<UdpSocket>> = HashMap::new();
+    for e in config.client.iter().chain(config.server.iter()) {
+        let Ok(socket) = UdpSocket::bind(config.address_of(e.id)) else {
+            eprintln!("Node {}: can't bind {}", e.id, config.address_of(e.id));
+            continue;
+        };
+        let socket = Arc::new(socket);
+        let listener = socket.clone();
+        let id = e.id;
+        thread::spawn(move || {
+            let mut buf = vec![0u8; 65507];
+            while let Ok((len, _)) = listener.recv_from(&mut buf) {
+                if let Ok(Frame::Packet(packet)) = decode_frame(&buf[..len]) {
+                    println!("[node {}] received {:?}", id, packet);
+                }
+            }
+        });
+        edges.insert(e.id, socket);
+    }
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let arg = |i: usize| words.get(i).and_then(|w| w.parse::<NodeId>().ok());
+        let command = match (words.first().copied(), arg(1)) {
+            (Some("quit"), _) => break,
+            (Some("kill"), Some(id)) => {
+                match children.get_mut(&id).map(|child| child.kill()) {
+                    Some(Ok(_)) => println!("Killed drone {}", id),
+                    Some(Err(er)) => println!("Failed killing drone {} [{}]", id, er),
+                    None => println!("Unknown drone {}", id),
+                }
+                continue;
+            }
+            (Some("flood"), Some(client)) => {
+                let flood_id = words.get(2).and_then(|w| w.parse().ok()).unwrap_or(0);
+                flood_from(config, &edges, client, flood_id);
+                continue;
+            }
+            (Some("crash"), Some(id)) => Some((id, RemoteCommand::Crash)),
+            (Some("pdr"), Some(id)) => words
+                .get(2)
+                .and_then(|w| w.parse().ok())
+                .map(|pdr| (id, RemoteCommand::SetPacketDropRate(pdr))),
+            (Some("add"), Some(id)) => {
+                arg(2).map(|n| (id, RemoteCommand::AddSender(n, config.address_of(n))))
+            }
+            (Some("remove"), Some(id)) => arg(2).map(|n| (id, RemoteCommand::RemoveSender(n))),
+            _ => None,
+        };
+        match command {
+            Some((id, command)) => match encode_frame(&Frame::Command(command)) {
+                Ok(bytes) => {
+                    let _ = controller.send_to(&bytes, config.address_of(id));
+                }
+                Err(er) => println!("{}", er),
+            },
+            None => println!("{}", USAGE),
+        }
+    }
+    for (_, mut child) in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+fn flood_from(
+    config: &NetworkConfig,
+    edges: &HashMap<NodeId, Arc<UdpSocket>>,
+    client: NodeId,
+    flood_id: u64,
+) {
+    let Some(socket) = edges.get(&client) else {
+        println!("Unknown client {}", client);
+        return;
+    };
+    let packet = Packet {
+        session_id: flood_id,
+        routing_header: SourceRoutingHeader {
+            hop_index: 0,
+            hops: vec![],
+        },
+        pack_type: PacketType::FloodRequest(FloodRequest {
+            flood_id,
+            initiator_id: client,
+            path_trace: vec![(client, NodeType::Client)],
+        }),
+    };
+    let bytes = match encode_frame(&Frame::Packet(packet)) {
+        Ok(bytes) => bytes,
+        Err(er) => {
+            println!("{}", er);
+            return;
+        }
+    };
+    for neighbour in config.neighbours_of(client) {
+        let _ = socket.send_to(&bytes, config.address_of(neighbour));
+    }
+}
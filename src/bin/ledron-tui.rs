@@ -0,0 +1,364 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Cell, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use std::{env, io, process};
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+use LeDron_James::config::NetworkConfig;
+use LeDron_James::network::{Network, NetworkEvent};
+
+const HELP: &str = "q quit | ↑↓ select | c crash | r recover | p set pdr | / filter log (n <node> / s <session>) | : command (flood <node>, send <n> <session> <path..>) | PgUp/PgDn scroll";
+const LOG_CAPACITY: usize = 5000;
+
+struct LogLine {
+    node: NodeId,
+    session: u64,
+    text: String,
+}
+enum Filter {
+    All,
+    Node(NodeId),
+    Session(u64),
+}
+enum Input {
+    Normal,
+    Pdr(String),
+    Filter(String),
+    Command(String),
+}
+
+struct App {
+    network: Network,
+    drones: Vec<NodeId>,
+    table: TableState,
+    log: VecDeque<LogLine>,
+    log_offset: usize, // Lines scrolled up from the bottom
+    filter: Filter,
+    input: Input,
+    message: String,
+    rates: HashMap<NodeId, (f64, f64)>, // Forwarded/s, dropped/s
+    last_sample: (Instant, HashMap<NodeId, (u64, u64)>),
+}
+
+fn main() -> io::Result<()> {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: ledron-tui <topology.json>");
+        process::exit(2);
+    };
+    let config = NetworkConfig::from_file(&path).unwrap_or_else(|er| {
+        eprintln!("{}", er);
+        process::exit(1);
+    });
+    let network = Network::spawn(config, false);
+    let drones: Vec<NodeId> = (network.node_ids().into_iter())
+        .filter(|id| network.is_drone(*id))
+        .collect();
+    let mut app = App {
+        network,
+        drones,
+        table: TableState::default().with_selected(Some(0)),
+        log: VecDeque::new(),
+        log_offset: 0,
+        filter: Filter::All,
+        input: Input::Normal,
+        message: HELP.to_string(),
+        rates: HashMap::new(),
+        last_sample: (Instant::now(), HashMap::new()),
+    };
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    app.network.shutdown();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            self.collect();
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let input = std::mem::replace(&mut self.input, Input::Normal);
+            self.input = match (input, key.code) {
+                (Input::Normal, KeyCode::Char('q')) => return Ok(()),
+                (Input::Normal, KeyCode::Up) => {
+                    self.table.select_previous();
+                    Input::Normal
+                }
+                (Input::Normal, KeyCode::Down) => {
+                    self.table.select_next();
+                    Input::Normal
+                }
+                (Input::Normal, KeyCode::PageUp) => {
+                    self.log_offset += 10;
+                    Input::Normal
+                }
+                (Input::Normal, KeyCode::PageDown) => {
+                    self.log_offset = self.log_offset.saturating_sub(10);
+                    Input::Normal
+                }
+                (Input::Normal, KeyCode::Char('c')) => {
+                    if let Some(id) = self.selected() {
+                        let result = self.network.crash(id);
+                        self.report(result, format!("Crashed drone {}", id));
+                    }
+                    Input::Normal
+                }
+                (Input::Normal, KeyCode::Char('r')) => {
+                    if let Some(id) = self.selected() {
+                        let result = self.network.recover(id);
+                        self.report(result, format!("Recovered drone {}", id));
+                    }
+                    Input::Normal
+                }
+                (Input::Normal, KeyCode::Char('p')) => Input::Pdr(String::new()),
+                (Input::Normal, KeyCode::Char('/')) => Input::Filter(String::new()),
+                (Input::Normal, KeyCode::Char(':')) => Input::Command(String::new()),
+                (Input::Normal, _) => Input::Normal,
+                (_, KeyCode::Esc) => Input::Normal,
+                (Input::Pdr(text), KeyCode::Enter) => {
+                    self.submit_pdr(&text);
+                    Input::Normal
+                }
+                (Input::Filter(text), KeyCode::Enter) => {
+                    self.submit_filter(&text);
+                    Input::Normal
+                }
+                (Input::Command(text), KeyCode::Enter) => {
+                    self.submit_command(&text);
+                    Input::Normal
+                }
+                (input, code) => edit(input, code),
+            };
+        }
+    }
+    fn selected(&self) -> Option<NodeId> {
+        self.table
+            .selected()
+            .and_then(|i| self.drones.get(i))
+            .copied()
+    }
+    fn report(&mut self, result: Result<(), String>, ok: String) {
+        self.message = match result {
+            Ok(()) => ok,
+            Err(er) => er,
+        };
+    }
+    fn submit_pdr(&mut self, text: &str) {
+        let Some(id) = self.selected() else { return };
+        match text.trim().parse::<f32>() {
+            Ok(pdr) if (0.0..=1.0).contains(&pdr) => {
+                let result = self.network.set_pdr(id, pdr);
+                self.report(result, format!("Drone {} pdr set to {}", id, pdr));
+            }
+            _ => self.message = format!("Invalid pdr '{}'", text),
+        }
+    }
+    fn submit_filter(&mut self, text: &str) {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        self.filter = match words.as_slice() {
+            ["n", id] => id.parse().map(Filter::Node).unwrap_or(Filter::All),
+            ["s", session] => session.parse().map(Filter::Session).unwrap_or(Filter::All),
+            _ => Filter::All,
+        };
+        self.log_offset = 0;
+    }
+    fn submit_command(&mut self, text: &str) {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let numbers: Vec<u64> = words
+            .iter()
+            .skip(1)
+            .filter_map(|w| w.parse().ok())
+            .collect();
+        let result = match (words.first().copied(), numbers.as_slice()) {
+            (Some("flood"), [node]) => self.network.flood(*node as NodeId, rand_flood_id()),
+            (Some("send"), [count, session, path @ ..]) => {
+                let path = path.iter().map(|id| *id as NodeId).collect();
+                self.network.send_fragments(path, *count, *session)
+            }
+            _ => Err(format!("Unknown command '{}'", text)),
+        };
+        self.report(result, format!("Done: {}", text));
+    }
+
+    // Polls the network, fills the log and refreshes the per-second rates.
+    fn collect(&mut self) {
+        for event in self.network.poll() {
+            let line = match event {
+                NetworkEvent::Drone(id, event) => {
+                    let (what, packet) = match event {
+                        DroneEvent::PacketSent(p) => ("sent", p),
+                        DroneEvent::PacketDropped(p) => ("DROPPED", p),
+                        DroneEvent::ControllerShortcut(p) => ("shortcut", p),
+                    };
+                    LogLine {
+                        node: id,
+                        session: packet.session_id,
+                        text: format!("drone {:>3} {:<8} {}", id, what, describe(&packet)),
+                    }
+                }
+                NetworkEvent::Received(id, packet) => LogLine {
+                    node: id,
+                    session: packet.session_id,
+                    text: format!("node  {:>3} received {}", id, describe(&packet)),
+                },
+            };
+            self.log.push_back(line);
+            if self.log.len() > LOG_CAPACITY {
+                self.log.pop_front();
+            }
+        }
+        let elapsed = self.last_sample.0.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let mut sample = HashMap::new();
+            for id in &self.drones {
+                let traffic = self.network.traffic_of(*id);
+                let (fwd, drop) = (self.last_sample.1.get(id).copied()).unwrap_or_default();
+                let secs = elapsed.as_secs_f64();
+                self.rates.insert(
+                    *id,
+                    (
+                        (traffic.forwarded - fwd) as f64 / secs,
+                        (traffic.dropped - drop) as f64 / secs,
+                    ),
+                );
+                sample.insert(*id, (traffic.forwarded, traffic.dropped));
+            }
+            self.last_sample = (Instant::now(), sample);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, log_area, status_area] = Layout::vertical([
+            Constraint::Length(self.drones.len() as u16 + 3),
+            Constraint::Min(5),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+
+        let rows = self.drones.iter().map(|id| {
+            let status = self.network.status_of(*id).unwrap_or_default();
+            let crashed = self.network.is_crashed(*id);
+            let (fwd, drop) = self.rates.get(id).copied().unwrap_or_default();
+            let traffic = self.network.traffic_of(*id);
+            let row = Row::new(vec![
+                Cell::from(id.to_string()),
+                Cell::from(format!("{:.2}", self.network.pdr_of(*id).unwrap_or(0.0))),
+                Cell::from(if crashed { "CRASHED" } else { "" }),
+                Cell::from(self.network.neighbours_of(*id).len().to_string()),
+                Cell::from(format!("{:.1}", fwd)),
+                Cell::from(format!("{:.1}", drop)),
+                Cell::from(traffic.forwarded.to_string()),
+                Cell::from(traffic.dropped.to_string()),
+                Cell::from(status.flood_history.to_string()),
+                Cell::from(status.queue_depth.to_string()),
+            ]);
+            if crashed {
+                row.style(Style::default().fg(Color::Red))
+            } else {
+                row
+            }
+        });
+        let header = Row::new(vec![
+            "id", "pdr", "state", "neigh", "fwd/s", "drop/s", "fwd", "dropped", "floods", "queue",
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+        let table = Table::new(rows, [Constraint::Length(8); 10])
+            .header(header)
+            .block(Block::bordered().title(" drones "))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let visible = log_area.height.saturating_sub(2) as usize;
+        let lines: Vec<&LogLine> = (self.log.iter())
+            .filter(|line| match self.filter {
+                Filter::All => true,
+                Filter::Node(id) => line.node == id,
+                Filter::Session(session) => line.session == session,
+            })
+            .collect();
+        self.log_offset = self.log_offset.min(lines.len().saturating_sub(visible));
+        let end = lines.len() - self.log_offset;
+        let items: Vec<ListItem> = lines[end.saturating_sub(visible)..end]
+            .iter()
+            .map(|line| {
+                let style = if line.text.contains("DROPPED") || line.text.contains("Nack") {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(line.text.as_str()).style(style)
+            })
+            .collect();
+        let title = match self.filter {
+            Filter::All => " events ".to_string(),
+            Filter::Node(id) => format!(" events of node {} ", id),
+            Filter::Session(session) => format!(" events of session {} ", session),
+        };
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(title)),
+            log_area,
+        );
+
+        let status = match &self.input {
+            Input::Normal => self.message.clone(),
+            Input::Pdr(text) => format!("new pdr for drone {:?}: {}_", self.selected(), text),
+            Input::Filter(text) => format!("filter (n <node> | s <session> | empty): {}_", text),
+            Input::Command(text) => format!(":{}_", text),
+        };
+        frame.render_widget(Paragraph::new(status).block(Block::bordered()), status_area);
+    }
+}
+
+// Typing into one of the prompts.
+fn edit(input: Input, code: KeyCode) -> Input {
+    let (mut text, wrap): (String, fn(String) -> Input) = match input {
+        Input::Pdr(text) => (text, Input::Pdr),
+        Input::Filter(text) => (text, Input::Filter),
+        Input::Command(text) => (text, Input::Command),
+        Input::Normal => return Input::Normal,
+    };
+    match code {
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::Backspace => {
+            text.pop();
+        }
+        _ => {}
+    }
+    wrap(text)
+}
+
+fn describe(packet: &Packet) -> String {
+    let kind = match &packet.pack_type {
+        PacketType::MsgFragment(f) => {
+            format!("Fragment {}/{}", f.fragment_index + 1, f.total_n_fragments)
+        }
+        PacketType::Ack(a) => format!("Ack {}", a.fragment_index),
+        PacketType::Nack(n) => format!("Nack {:?} {}", n.nack_type, n.fragment_index),
+        PacketType::FloodRequest(f) => {
+            format!("FloodRequest {} from {}", f.flood_id, f.initiator_id)
+        }
+        PacketType::FloodResponse(f) => format!("FloodResponse {}", f.flood_id),
+    };
+    format!(
+        "[session {}] {} hops {:?}@{}",
+        packet.session_id, kind, packet.routing_header.hops, packet.routing_header.hop_index
+    )
+}
+
+fn rand_flood_id() -> u64 {
+    rand::random()
+}
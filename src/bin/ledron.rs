@@ -0,0 +1,312 @@
+use std::io::{BufRead, Write};
+use std::time::Duration;
+use std::{env, process};
+use wg_2024::network::NodeId;
+use wg_2024::packet::PacketType;
+use LeDron_James::builder::{DroneConfig, LossModel};
+use LeDron_James::config::NetworkConfig;
+use LeDron_James::control::{ControlCommand, LogLevel};
+use LeDron_James::export::NetworkSnapshot;
+use LeDron_James::network::{Network, NetworkEvent};
+use LeDron_James::scenario::Scenario;
+use LeDron_James::snapshot::NetworkCheckpoint;
+
+const USAGE: &str = "usage: ledron [--log] [--verbose] [--scenario <file>] [--metrics <port>]
+              [--drone-config <file>] <topology.json>
+
+With --scenario the file is executed (see LeDron_James::scenario) and the exit code is 1
+if any expectation failed, the network state is then saved next to the scenario file.
+--drone-config applies a DroneConfig JSON file (seed, queue size, profile...) to every drone.
+With --metrics (built with the `metrics` feature) the drones are exposed to Prometheus
+on http://127.0.0.1:<port>/metrics.
+
+Commands are read from stdin, one per line (pipe a file to script a session):
+  flood <node> [flood-id]           send a FloodRequest from a client/server/drone
+  send <n> <session> <path...>      send n fragments along path (first id is the sender)
+  crash <drone>                     crash a drone and detach it from its neighbours
+  recover <drone>                   restart a crashed drone with its configured links
+  pdr <drone> <value>               change the packet drop rate
+  log <drone> <on|off|info|debug>  toggle logging or change the log level of a drone
+  loss <drone> uniform              drop fragments with probability PDR (default)
+  loss <drone> burst <enter> <leave> <burst-pdr>
+                                    Gilbert-Elliott bursts of losses
+  reset-stats <drone>               reset the drone counters
+  clear-floods <drone>              forget the floods the drone has seen
+  state <drone>                     ask a drone for its current state
+  dump [drone]                      full state of a drone (all of them without id) as JSON
+  checkpoint <file>                 save the state of every drone (settle the network first)
+  restore <file>                    replace the network with a saved checkpoint
+  wait <ms>                         let the network run for a while
+  stats                             print the counters so far
+  export <dot|json> [file]          dump the network state (stdout without a file)
+  quit                              stop the drones and print the summary";
+
+fn main() {
+    let mut logging = false;
+    let mut verbose = false;
+    let mut topology = None;
+    let mut scenario = None;
+    let mut metrics_port: Option<u16> = None;
+    let mut drone_config = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log" => logging = true,
+            "--scenario" => scenario = args.next(),
+            "--drone-config" => drone_config = args.next(),
+            "--metrics" => metrics_port = args.next().and_then(|p| p.parse().ok()),
+            "--verbose" | "-v" => verbose = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => topology = Some(arg),
+        }
+    }
+    let Some(topology) = topology else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+    let config = NetworkConfig::from_file(&topology).unwrap_or_else(|er| {
+        eprintln!("{}", er);
+        process::exit(1);
+    });
+    let drone_config = match drone_config {
+        Some(path) => DroneConfig::from_file(&path),
+        None => Ok(DroneConfig::default()),
+    };
+    let mut network = drone_config
+        .and_then(|drone_config| {
+            Network::spawn_with(
+                config,
+                DroneConfig {
+                    logging,
+                    ..drone_config
+                },
+            )
+        })
+        .unwrap_or_else(|er| {
+            eprintln!("{}", er);
+            process::exit(1);
+        });
+    println!("Spawned nodes {:?}", network.node_ids());
+    if let Some(port) = metrics_port {
+        serve_metrics(&network, port);
+    }
+    if let Some(path) = scenario {
+        run_scenario(network, &path);
+        return;
+    }
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else { break };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first() == Some(&"quit") {
+            break;
+        }
+        if let Err(er) = run_command(&mut network, &words) {
+            println!("{}", er);
+        }
+        report(&network.poll(), verbose);
+    }
+    report(&network.settle(Duration::from_millis(100)), verbose);
+    let stats = network.shutdown();
+    println!(
+        "delivered: {}, dropped: {}, nacked: {} (sent events: {}, shortcuts: {})",
+        stats.delivered, stats.dropped, stats.nacked, stats.sent, stats.shortcuts
+    );
+}
+
+fn run_scenario(mut network: Network, path: &str) {
+    let scenario = Scenario::from_file(path).unwrap_or_else(|er| {
+        eprintln!("{}", er);
+        process::exit(1);
+    });
+    let report = scenario.run(&mut network, Duration::from_millis(200));
+    if !report.success() {
+        let snapshot = NetworkSnapshot::capture(&network);
+        for (extension, text) in [("dot", snapshot.to_dot()), ("json", snapshot.to_json())] {
+            let file = format!("{}.failure.{}", path, extension);
+            match std::fs::write(&file, text) {
+                Ok(_) => println!("Network state saved to {}", file),
+                Err(er) => println!("{}: {}", file, er),
+            }
+        }
+    }
+    let stats = network.shutdown();
+    for passed in &report.passed {
+        println!("PASS {}", passed);
+    }
+    for failure in &report.failures {
+        println!("FAIL {}", failure);
+    }
+    println!(
+        "delivered: {}, dropped: {}, nacked: {}",
+        stats.delivered, stats.dropped, stats.nacked
+    );
+    if !report.success() {
+        process::exit(1);
+    }
+}
+
+fn run_command(network: &mut Network, words: &[&str]) -> Result<(), String> {
+    let id = |i: usize| -> Result<NodeId, String> {
+        let word = words.get(i).ok_or(USAGE.to_string())?;
+        word.parse()
+            .map_err(|_| format!("Invalid node id '{}'", word))
+    };
+    let number = |i: usize| -> Result<u64, String> {
+        let word = words.get(i).ok_or(USAGE.to_string())?;
+        word.parse()
+            .map_err(|_| format!("Invalid number '{}'", word))
+    };
+    match words.first().copied() {
+        None => Ok(()),
+        Some("flood") => network.flood(id(1)?, number(2).unwrap_or(0)),
+        Some("send") => {
+            let path = (3..words.len()).map(id).collect::<Result<Vec<_>, _>>()?;
+            network.send_fragments(path, number(1)?, number(2)?)
+        }
+        Some("crash") => network.crash(id(1)?),
+        Some("recover") => network.recover(id(1)?),
+        Some("pdr") => {
+            let word = words.get(2).ok_or(USAGE.to_string())?;
+            let pdr: f32 = word
+                .parse()
+                .map_err(|_| format!("Invalid pdr '{}'", word))?;
+            if !(0.0..=1.0).contains(&pdr) {
+                return Err("PDR must be between 0 and 1".to_string());
+            }
+            network.set_pdr(id(1)?, pdr)
+        }
+        Some("log") => {
+            let command = match words.get(2).copied() {
+                Some("on") => ControlCommand::SetLogging(true),
+                Some("off") => ControlCommand::SetLogging(false),
+                Some("info") => ControlCommand::SetLogLevel(LogLevel::Info),
+                Some("debug") => ControlCommand::SetLogLevel(LogLevel::Debug),
+                _ => return Err(USAGE.to_string()),
+            };
+            network.control(id(1)?, command)
+        }
+        Some("loss") => {
+            let probability = |i: usize| -> Result<f64, String> {
+                let word = words.get(i).ok_or(USAGE.to_string())?;
+                word.parse()
+                    .map_err(|_| format!("Invalid probability '{}'", word))
+            };
+            let model = match words.get(2).copied() {
+                Some("uniform") => LossModel::Uniform,
+                Some("burst") => LossModel::Burst {
+                    enter: probability(3)?,
+                    leave: probability(4)?,
+                    burst_pdr: probability(5)?,
+                },
+                _ => return Err(USAGE.to_string()),
+            };
+            model.validate()?;
+            network.control(id(1)?, ControlCommand::SetLossModel(model))
+        }
+        Some("reset-stats") => network.control(id(1)?, ControlCommand::ResetStats),
+        Some("clear-floods") => network.control(id(1)?, ControlCommand::ClearFloodHistory),
+        Some("state") => {
+            println!("{:?}", network.query_state(id(1)?)?);
+            Ok(())
+        }
+        Some("dump") => {
+            let states = match words.get(1) {
+                Some(_) => vec![(id(1)?, network.dump_state(id(1)?))],
+                None => network.dump_all(),
+            };
+            for (id, state) in states {
+                match state
+                    .and_then(|s| serde_json::to_string_pretty(&s).map_err(|er| er.to_string()))
+                {
+                    Ok(json) => println!("{}", json),
+                    Err(er) => println!(
+                        "drone {}: no answer [{}], last status {:?}",
+                        id,
+                        er,
+                        network.status_of(id)
+                    ),
+                }
+            }
+            Ok(())
+        }
+        Some("checkpoint") => {
+            let file = words.get(1).ok_or(USAGE.to_string())?;
+            network.settle(Duration::from_millis(50));
+            network.checkpoint()?.save(file)
+        }
+        Some("restore") => {
+            let file = words.get(1).ok_or(USAGE.to_string())?;
+            let restored = Network::restore(NetworkCheckpoint::load(file)?)?;
+            std::mem::replace(network, restored).shutdown();
+            Ok(())
+        }
+        Some("wait") => {
+            std::thread::sleep(Duration::from_millis(number(1)?));
+            Ok(())
+        }
+        Some("export") => {
+            network.poll();
+            let snapshot = NetworkSnapshot::capture(network);
+            let text = match words.get(1).copied() {
+                Some("dot") => snapshot.to_dot(),
+                Some("json") => snapshot.to_json(),
+                _ => return Err(USAGE.to_string()),
+            };
+            match words.get(2) {
+                Some(file) => std::fs::write(file, text).map_err(|er| format!("{}: {}", file, er)),
+                None => {
+                    println!("{}", text);
+                    Ok(())
+                }
+            }
+        }
+        Some("stats") => {
+            println!("{:?}", network.stats());
+            Ok(())
+        }
+        Some(_) => Err(USAGE.to_string()),
+    }
+}
+
+fn report(events: &[NetworkEvent], verbose: bool) {
+    for event in events {
+        match event {
+            NetworkEvent::Received(id, packet) => match &packet.pack_type {
+                PacketType::Nack(nack) => println!(
+                    "node {} <- Nack {:?} [session {}, fragment {}]",
+                    id, nack.nack_type, packet.session_id, nack.fragment_index
+                ),
+                PacketType::MsgFragment(fragment) if !verbose => println!(
+                    "node {} <- fragment {}/{} [session {}]",
+                    id,
+                    fragment.fragment_index + 1,
+                    fragment.total_n_fragments,
+                    packet.session_id
+                ),
+                _ => println!("node {} <- {:?}", id, packet.pack_type),
+            },
+            NetworkEvent::Drone(id, event) if verbose => println!("drone {}: {:?}", id, event),
+            NetworkEvent::Drone(..) => {}
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn serve_metrics(network: &Network, port: u16) {
+    match LeDron_James::metrics::serve(port, network.status_board()) {
+        Ok(addr) => println!("Metrics on http://{}/metrics", addr),
+        Err(er) => eprintln!("Can't serve metrics on port {}: {}", port, er),
+    }
+}
+#[cfg(not(feature = "metrics"))]
+fn serve_metrics(_network: &Network, _port: u16) {
+    eprintln!("--metrics needs ledron built with the `metrics` feature");
+}
@@ -0,0 +1,347 @@
+use crate::pipeline::PacketHook;
+use crate::snapshot::DroneSnapshot;
+use crate::Drone;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use wg_2024::controller::*;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+/// How the drone treats the fragments it forwards.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Profile {
+    #[default]
+    Standard, // WGL behaviour, fragments are dropped with probability PDR
+    Lossless, // PDR is ignored, handy to test a topology before adding losses
+}
+
+/// What a crashed drone does with the FloodRequests still in its queue, both are reported
+/// to the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum CrashedFloodPolicy {
+    #[default]
+    Drop, // Reported as PacketDropped, the flood just doesn't go through us
+    Respond, // FloodResponse ending with us, the initiator learns we're a dead end
+}
+
+/// Order in which the drone handles the packets it received, see `scheduler::Scheduler`.
+/// Except with Fifo, up to `queue_size` packets (1024 without one) are taken from the channel
+/// and reordered.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Scheduling {
+    #[default]
+    Fifo, // Channel order, packets aren't queued internally
+    Priority, // Control packets (Ack, Nack, FloodResponse) first, then FloodRequests, then fragments
+    // Round robin serving up to that many packets of each class per round, nobody starves
+    Weighted {
+        control: u32,
+        flood: u32,
+        fragment: u32,
+    },
+}
+
+/// When a FloodRequest counts as already seen, which gets it answered instead of forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum FloodDedup {
+    #[default]
+    Wgl, // Same initiator and flood_id
+    Session, // Same initiator, flood_id and session_id
+    // The first K arrivals of a flood are forwarded, enumerating more paths for more traffic.
+    // An arrival whose path trace already has us is answered: it went around a loop.
+    Paths(usize),
+}
+
+/// Token bucket applied to the new floods of every initiator: `burst` floods at once,
+/// then `rate` per second. Floods already seen don't take a token.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FloodRateLimit {
+    pub rate: f64,
+    pub burst: u32,
+    #[serde(default)]
+    pub excess: ExcessFloodPolicy,
+}
+
+/// What happens to a flood over the limit, reported to the controller as dropped either way.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ExcessFloodPolicy {
+    #[default]
+    Drop, // The initiator won't hear about us
+    Respond, // FloodResponse ending with us, without forwarding
+}
+
+/// What decides whether a fragment is dropped, switchable at runtime through `ControlCommand`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LossModel {
+    #[default]
+    Uniform, // Every fragment is dropped with probability PDR
+    // Gilbert-Elliott: before every fragment we enter a burst with probability `enter` (leave it
+    // with `leave`), during a burst fragments are dropped with `burst_pdr` instead of the PDR.
+    Burst {
+        enter: f64,
+        leave: f64,
+        burst_pdr: f64,
+    },
+}
+
+/// Everything `wg_2024::drone::Drone::new` can't take. The defaults are what the trait
+/// constructor uses, every field can be omitted in the JSON file.
+/// ```json
+/// { "logging": false, "seed": 42, "queue_size": 1024, "flood_history_limit": 64, "profile": "Standard",
+///   "loss_model": { "Burst": { "enter": 0.05, "leave": 0.3, "burst_pdr": 0.8 } }, "crashed_floods": "Respond",
+///   "flood_rate_limit": { "rate": 2.0, "burst": 5, "excess": "Drop" }, "flood_dedup": { "Paths": 3 },
+///   "scheduling": { "Weighted": { "control": 4, "flood": 1, "fragment": 2 } } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DroneConfig {
+    pub logging: bool,
+    pub seed: Option<u64>, // Reproducible drops (mixed with the id), thread rng if None
+    pub queue_size: Option<usize>, // Packet channel capacity, senders block when full
+    pub flood_history_limit: Option<usize>, // Flood ids kept per initiator, oldest go first
+    pub profile: Profile,
+    pub loss_model: LossModel,
+    pub crashed_floods: CrashedFloodPolicy,
+    pub flood_rate_limit: Option<FloodRateLimit>, // No limit if None
+    pub flood_dedup: FloodDedup,
+    pub scheduling: Scheduling,
+}
+
+impl Default for DroneConfig {
+    fn default() -> Self {
+        Self {
+            logging: true,
+            seed: None,
+            queue_size: None,
+            flood_history_limit: None,
+            profile: Profile::Standard,
+            loss_model: LossModel::Uniform,
+            crashed_floods: CrashedFloodPolicy::Drop,
+            flood_rate_limit: None,
+            flood_dedup: FloodDedup::Wgl,
+            scheduling: Scheduling::Fifo,
+        }
+    }
+}
+
+impl DroneConfig {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|er| format!("{}: {}", path, er))?;
+        let config: Self = serde_json::from_str(&text).map_err(|er| format!("{}: {}", path, er))?;
+        config.validate()?;
+        Ok(config)
+    }
+    /// The file at `LEDRON_CONFIG` (every setting, see `from_file`) or the defaults, overridden
+    /// by `LEDRON_LOGGING`, `LEDRON_SEED`, `LEDRON_QUEUE_SIZE`, `LEDRON_FLOOD_HISTORY_LIMIT`,
+    /// `LEDRON_PROFILE` and `LEDRON_CRASHED_FLOODS` when set.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = match env::var("LEDRON_CONFIG") {
+            Ok(path) => Self::from_file(&path).map_err(|er| format!("LEDRON_CONFIG: {}", er))?,
+            Err(_) => Self::default(),
+        };
+        if let Some(logging) = env_var("LEDRON_LOGGING")? {
+            config.logging = logging;
+        }
+        config.seed = env_var("LEDRON_SEED")?.or(config.seed);
+        config.queue_size = env_var("LEDRON_QUEUE_SIZE")?.or(config.queue_size);
+        config.flood_history_limit =
+            env_var("LEDRON_FLOOD_HISTORY_LIMIT")?.or(config.flood_history_limit);
+        if let Ok(profile) = env::var("LEDRON_PROFILE") {
+            config.profile = match profile.as_str() {
+                "Standard" | "standard" => Profile::Standard,
+                "Lossless" | "lossless" => Profile::Lossless,
+                _ => return Err(format!("LEDRON_PROFILE: unknown profile '{}'", profile)),
+            };
+        }
+        if let Ok(policy) = env::var("LEDRON_CRASHED_FLOODS") {
+            config.crashed_floods = match policy.as_str() {
+                "Drop" | "drop" => CrashedFloodPolicy::Drop,
+                "Respond" | "respond" => CrashedFloodPolicy::Respond,
+                _ => {
+                    return Err(format!(
+                        "LEDRON_CRASHED_FLOODS: unknown policy '{}'",
+                        policy
+                    ))
+                }
+            };
+        }
+        config.validate()?;
+        Ok(config)
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        if self.queue_size == Some(0) {
+            return Err("queue_size must be at least 1".to_string());
+        }
+        if self.flood_history_limit == Some(0) {
+            // Every flood would be forwarded again and again.
+            return Err("flood_history_limit must be at least 1".to_string());
+        }
+        if let Scheduling::Weighted {
+            control,
+            flood,
+            fragment,
+        } = self.scheduling
+        {
+            if control == 0 || flood == 0 || fragment == 0 {
+                return Err(format!("{:?}: weights must be at least 1", self.scheduling));
+            }
+        }
+        if self.flood_dedup == FloodDedup::Paths(0) {
+            return Err("FloodDedup::Paths needs at least 1 path".to_string());
+        }
+        if let Some(limit) = self.flood_rate_limit {
+            if !(limit.rate.is_finite() && limit.rate > 0.0) || limit.burst == 0 {
+                return Err(format!(
+                    "{:?}: rate must be positive and burst at least 1",
+                    limit
+                ));
+            }
+        }
+        self.loss_model.validate()
+    }
+    /// Channel a drone with this config should receive its packets from.
+    pub fn packet_channel(&self) -> (Sender<Packet>, Receiver<Packet>) {
+        match self.queue_size {
+            Some(size) => bounded(size),
+            None => unbounded(),
+        }
+    }
+}
+
+impl LossModel {
+    pub fn validate(&self) -> Result<(), String> {
+        if let LossModel::Burst {
+            enter,
+            leave,
+            burst_pdr,
+        } = self
+        {
+            if [enter, leave, burst_pdr]
+                .iter()
+                .any(|p| !(0.0..=1.0).contains(*p))
+            {
+                return Err(format!("{:?}: probabilities must be within 0 and 1", self));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match env::var(name) {
+        Ok(value) => {
+            (value.parse().map(Some)).map_err(|_| format!("{}: invalid value '{}'", name, value))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Builds a `Drone` with a `DroneConfig`, checking the settings first.
+/// ```ignore
+/// let drone = DroneBuilder::new(1)
+///     .config(DroneConfig::from_env()?)
+///     .controller(event_send, command_recv)
+///     .packet_recv(packet_recv)
+///     .neighbours(packet_send)
+///     .pdr(0.1)
+///     .build()?;
+/// ```
+pub struct DroneBuilder {
+    id: NodeId,
+    pdr: f32,
+    config: DroneConfig,
+    controller: Option<(Sender<DroneEvent>, Receiver<DroneCommand>)>,
+    packet_recv: Option<Receiver<Packet>>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    snapshot: Option<DroneSnapshot>,
+    hooks: Vec<Box<dyn PacketHook>>,
+}
+
+impl DroneBuilder {
+    pub fn new(id: NodeId) -> Self {
+        Self {
+            id,
+            pdr: 0.0,
+            config: DroneConfig::default(),
+            controller: None,
+            packet_recv: None,
+            packet_send: HashMap::new(),
+            snapshot: None,
+            hooks: Vec::new(),
+        }
+    }
+    /// Drone picking up the state of `snapshot`, still to be wired to its channels.
+    pub fn from_snapshot(snapshot: DroneSnapshot) -> Self {
+        let mut builder = Self::new(snapshot.state.id)
+            .pdr(snapshot.state.pdr)
+            .config(snapshot.state.config.clone());
+        builder.snapshot = Some(snapshot);
+        builder
+    }
+    pub fn config(mut self, config: DroneConfig) -> Self {
+        self.config = config;
+        self
+    }
+    pub fn pdr(mut self, pdr: f32) -> Self {
+        self.pdr = pdr;
+        self
+    }
+    pub fn controller(
+        mut self,
+        controller_send: Sender<DroneEvent>,
+        controller_recv: Receiver<DroneCommand>,
+    ) -> Self {
+        self.controller = Some((controller_send, controller_recv));
+        self
+    }
+    pub fn packet_recv(mut self, packet_recv: Receiver<Packet>) -> Self {
+        self.packet_recv = Some(packet_recv);
+        self
+    }
+    pub fn neighbour(mut self, id: NodeId, sender: Sender<Packet>) -> Self {
+        self.packet_send.insert(id, sender);
+        self
+    }
+    pub fn neighbours(mut self, packet_send: HashMap<NodeId, Sender<Packet>>) -> Self {
+        self.packet_send.extend(packet_send);
+        self
+    }
+    /// Hooks run in the order they're added, see `PacketHook`.
+    pub fn hook(mut self, hook: Box<dyn PacketHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+    pub fn build(self) -> Result<Drone, String> {
+        self.config.validate()?;
+        if !(0.0..=1.0).contains(&self.pdr) {
+            return Err(format!("pdr must be within 0 and 1, got {}", self.pdr));
+        }
+        if self.packet_send.contains_key(&self.id) {
+            return Err(format!("Drone {} can't be its own neighbour", self.id));
+        }
+        let Some((controller_send, controller_recv)) = self.controller else {
+            return Err(format!("Drone {} has no controller channels", self.id));
+        };
+        let Some(packet_recv) = self.packet_recv else {
+            return Err(format!("Drone {} has no packet channel", self.id));
+        };
+        let mut drone = Drone::new(
+            self.id,
+            controller_send,
+            controller_recv,
+            packet_recv,
+            self.packet_send,
+            self.pdr,
+        );
+        drone.configure(self.config);
+        if let Some(snapshot) = self.snapshot {
+            drone.restore(snapshot);
+        }
+        for hook in self.hooks {
+            drone.add_hook(hook);
+        }
+        Ok(drone)
+    }
+}
@@ -0,0 +1,376 @@
+use crate::fragment::*;
+use crate::route_quality::RouteQuality;
+use crate::routing::reversed_route;
+use crate::topology::Topology;
+use crossbeam_channel::{Receiver, Sender};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+// Without an answer for this long we flood again for messages that have no route.
+const FLOOD_RETRY: Duration = Duration::from_millis(200);
+// A fragment nacked more than this is given up, together with its message.
+const MAX_RETRIES: u32 = 32;
+
+/// What the application asks the client to do.
+#[derive(Debug, Clone)]
+pub enum ClientCommand {
+    SendMessage(NodeId, Vec<u8>), // Destination, payload
+    Flood,
+}
+/// What the client reports back to the application.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    MessageReceived {
+        source: NodeId,
+        session_id: u64,
+        data: Vec<u8>,
+    },
+    MessageDelivered {
+        destination: NodeId,
+        session_id: u64,
+    }, // Every fragment got acked
+    MessageFailed {
+        destination: NodeId,
+        session_id: u64,
+    }, // Retries exhausted
+}
+
+// Message being sent, kept until every fragment is acked.
+struct Outgoing {
+    destination: NodeId,
+    fragments: Vec<Fragment>,
+    acked: HashSet<u64>,
+    retries: HashMap<u64, u32>,
+    route: Option<Vec<NodeId>>, // None until a flood tells us how to reach destination
+}
+
+/// Reference client: fragments messages, discovers the topology by flooding, source-routes
+/// fragments and reacts to Nacks (retransmit on Dropped, re-route on routing errors).
+/// Routes maximise the delivery probability estimated by `RouteQuality`.
+/// Incoming messages are reassembled and acknowledged.
+pub struct Client {
+    pub id: NodeId,
+    pub packet_recv: Receiver<Packet>,
+    pub packet_send: HashMap<NodeId, Sender<Packet>>,
+    pub command_recv: Receiver<ClientCommand>,
+    pub event_send: Sender<ClientEvent>,
+    topology: Topology,
+    quality: RouteQuality,
+    outgoing: HashMap<u64, Outgoing>,
+    reassembler: Reassembler,
+    session_counter: u64,
+    flood_counter: u64,
+    last_flood: Option<Instant>,
+    logging_enabled: bool,
+}
+
+impl Client {
+    pub fn new(
+        id: NodeId,
+        packet_recv: Receiver<Packet>,
+        packet_send: HashMap<NodeId, Sender<Packet>>,
+        command_recv: Receiver<ClientCommand>,
+        event_send: Sender<ClientEvent>,
+    ) -> Self {
+        let mut client = Self {
+            id,
+            packet_recv,
+            packet_send,
+            command_recv,
+            event_send,
+            topology: Topology::new(),
+            quality: RouteQuality::default(),
+            outgoing: HashMap::new(),
+            reassembler: Reassembler::new(),
+            session_counter: 0,
+            flood_counter: 0,
+            last_flood: None,
+            logging_enabled: true,
+        };
+        client.reset_topology();
+        client
+    }
+    pub fn logging_enabled(&mut self, log: bool) {
+        self.logging_enabled = log
+    }
+    pub fn run(&mut self) {
+        loop {
+            crossbeam_channel::select_biased! {
+                recv(self.command_recv) -> command => {
+                    match command {
+                        Ok(command) => self.handle_command(command),
+                        Err(_) => return,
+                    }
+                }
+                recv(self.packet_recv) -> packet => {
+                    match packet {
+                        Ok(packet) => self.handle_packet(packet),
+                        Err(_) => return,
+                    }
+                }
+                default(FLOOD_RETRY) => self.flood_if_stuck(),
+            }
+        }
+    }
+    fn log<S: AsRef<str>>(&self, message: S) {
+        #[cfg(feature = "log")]
+        if self.logging_enabled {
+            println!("LeClient ID {} - {}", self.id, message.as_ref());
+        }
+    }
+
+    fn handle_command(&mut self, command: ClientCommand) {
+        match command {
+            ClientCommand::SendMessage(destination, data) => {
+                // Session ids are unique per sender, the node id in the top byte keeps them
+                // unique in the network too.
+                self.session_counter += 1;
+                let session_id = ((self.id as u64) << 56) | self.session_counter;
+                let fragments = fragment_message(&data);
+                self.log(format!(
+                    "Sending {} fragments to {} [SESSION ID: {}]",
+                    fragments.len(),
+                    destination,
+                    session_id
+                ));
+                self.outgoing.insert(
+                    session_id,
+                    Outgoing {
+                        destination,
+                        fragments,
+                        acked: HashSet::new(),
+                        retries: HashMap::new(),
+                        route: None,
+                    },
+                );
+                self.send_session(session_id);
+            }
+            ClientCommand::Flood => self.flood(),
+        }
+    }
+    fn handle_packet(&mut self, packet: Packet) {
+        self.quality.record(&packet); // Only looks at Acks and Nacks
+        match packet.pack_type {
+            PacketType::MsgFragment(fragment) => {
+                let header = &packet.routing_header;
+                let Some(source) = header.hops.first().copied() else {
+                    return;
+                };
+                // Ack goes back along the path the fragment took.
+                let ack = Packet {
+                    session_id: packet.session_id,
+                    routing_header: reversed_route(header),
+                    pack_type: PacketType::Ack(Ack {
+                        fragment_index: fragment.fragment_index,
+                    }),
+                };
+                self.forward(ack);
+                if let Some(data) = self.reassembler.insert(packet.session_id, &fragment) {
+                    let _ = self.event_send.send(ClientEvent::MessageReceived {
+                        source,
+                        session_id: packet.session_id,
+                        data,
+                    });
+                }
+            }
+            PacketType::Ack(ack) => {
+                if let Some(outgoing) = self.outgoing.get_mut(&packet.session_id) {
+                    outgoing.acked.insert(ack.fragment_index);
+                    if outgoing.acked.len() == outgoing.fragments.len() {
+                        let destination = outgoing.destination;
+                        self.outgoing.remove(&packet.session_id);
+                        let _ = self.event_send.send(ClientEvent::MessageDelivered {
+                            destination,
+                            session_id: packet.session_id,
+                        });
+                    }
+                }
+            }
+            PacketType::Nack(nack) => {
+                // The first hop of a Nack is the drone that generated it.
+                let reporter = packet.routing_header.hops.first().copied();
+                self.handle_nack(packet.session_id, nack, reporter);
+            }
+            PacketType::FloodRequest(mut flood) => {
+                // Clients don't forward floods, they answer straight away.
+                flood.path_trace.push((self.id, NodeType::Client));
+                self.topology.ingest_path(&flood.path_trace);
+                let hops: Vec<NodeId> = flood.path_trace.iter().rev().map(|(id, _)| *id).collect();
+                let response = Packet {
+                    session_id: packet.session_id,
+                    routing_header: SourceRoutingHeader { hop_index: 1, hops },
+                    pack_type: PacketType::FloodResponse(FloodResponse {
+                        flood_id: flood.flood_id,
+                        path_trace: flood.path_trace,
+                    }),
+                };
+                self.forward(response);
+            }
+            PacketType::FloodResponse(response) => {
+                self.topology.ingest(&response);
+                self.route_pending();
+            }
+        }
+    }
+    fn handle_nack(&mut self, session_id: u64, nack: Nack, reporter: Option<NodeId>) {
+        self.log(format!(
+            "Nack {:?} [SESSION ID: {}]",
+            nack.nack_type, session_id
+        ));
+        let Some(outgoing) = self.outgoing.get_mut(&session_id) else {
+            return;
+        };
+        let retries = outgoing.retries.entry(nack.fragment_index).or_insert(0);
+        *retries += 1;
+        if *retries > MAX_RETRIES {
+            let destination = outgoing.destination;
+            self.outgoing.remove(&session_id);
+            let _ = self.event_send.send(ClientEvent::MessageFailed {
+                destination,
+                session_id,
+            });
+            return;
+        }
+        match nack.nack_type {
+            NackType::Dropped => outgoing.route = None, // Estimates changed, maybe the best route too
+            NackType::ErrorInRouting(unreachable) => {
+                outgoing.route = None;
+                if let Some(reporter) = reporter {
+                    self.topology.remove_edge(reporter, unreachable);
+                }
+            }
+            NackType::UnexpectedRecipient(_) | NackType::DestinationIsDrone => {
+                // Our picture of the network is wrong, start over.
+                self.reset_topology();
+                for outgoing in self.outgoing.values_mut() {
+                    outgoing.route = None;
+                }
+            }
+        }
+        self.send_fragment(session_id, nack.fragment_index);
+    }
+
+    // Sends every fragment not acked yet.
+    fn send_session(&mut self, session_id: u64) {
+        let Some(outgoing) = self.outgoing.get(&session_id) else {
+            return;
+        };
+        let pending: Vec<u64> = (0..outgoing.fragments.len() as u64)
+            .filter(|i| !outgoing.acked.contains(i))
+            .collect();
+        for index in pending {
+            self.send_fragment(session_id, index);
+        }
+    }
+    fn send_fragment(&mut self, session_id: u64, index: u64) {
+        let route = match self.outgoing.get(&session_id) {
+            Some(Outgoing {
+                route: Some(route), ..
+            }) => route.clone(),
+            Some(outgoing) => {
+                match self
+                    .quality
+                    .best_path(&self.topology, self.id, outgoing.destination)
+                {
+                    Some(route) => {
+                        if let Some(outgoing) = self.outgoing.get_mut(&session_id) {
+                            outgoing.route = Some(route.clone());
+                        }
+                        route
+                    }
+                    None => {
+                        // Sent again by route_pending once a FloodResponse shows a path.
+                        self.flood_if_stuck();
+                        return;
+                    }
+                }
+            }
+            None => return,
+        };
+        let Some(fragment) =
+            (self.outgoing.get(&session_id)).and_then(|o| o.fragments.get(index as usize).cloned())
+        else {
+            return;
+        };
+        self.forward(Packet {
+            session_id,
+            routing_header: SourceRoutingHeader {
+                hop_index: 1,
+                hops: route,
+            },
+            pack_type: PacketType::MsgFragment(fragment),
+        });
+    }
+    // Called when the topology grows: sends whatever was waiting for a route.
+    fn route_pending(&mut self) {
+        let waiting: Vec<u64> = (self.outgoing.iter())
+            .filter(|(_, o)| o.route.is_none())
+            .map(|(session_id, _)| *session_id)
+            .collect();
+        for session_id in waiting {
+            let destination = self.outgoing[&session_id].destination;
+            if let Some(route) = self.quality.best_path(&self.topology, self.id, destination) {
+                if let Some(outgoing) = self.outgoing.get_mut(&session_id) {
+                    outgoing.route = Some(route);
+                }
+                self.send_session(session_id);
+            }
+        }
+    }
+    // Sends to hops[hop_index], a missing neighbour is handled like an ErrorInRouting.
+    fn forward(&mut self, packet: Packet) {
+        let Some(next) = packet.routing_header.current_hop() else {
+            return;
+        };
+        let sent = match self.packet_send.get(&next) {
+            Some(channel) => channel.send(packet).is_ok(),
+            None => false,
+        };
+        if !sent {
+            self.log(format!("Neighbour {} unreachable", next));
+            self.topology.remove_edge(self.id, next);
+            for outgoing in self.outgoing.values_mut() {
+                outgoing.route = None;
+            }
+        }
+    }
+
+    fn flood(&mut self) {
+        self.flood_counter += 1;
+        self.session_counter += 1;
+        self.last_flood = Some(Instant::now());
+        let packet = Packet {
+            session_id: ((self.id as u64) << 56) | self.session_counter,
+            routing_header: SourceRoutingHeader {
+                hop_index: 0,
+                hops: vec![],
+            },
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id: self.flood_counter,
+                initiator_id: self.id,
+                path_trace: vec![(self.id, NodeType::Client)],
+            }),
+        };
+        for channel in self.packet_send.values() {
+            let _ = channel.send(packet.clone());
+        }
+    }
+    fn flood_if_stuck(&mut self) {
+        let stuck = self.outgoing.values().any(|o| o.route.is_none());
+        let stale = self.last_flood.is_none_or(|at| at.elapsed() >= FLOOD_RETRY);
+        if stuck && stale {
+            self.flood();
+        }
+    }
+    // Forgets everything but our own links: neighbours are drones by protocol.
+    fn reset_topology(&mut self) {
+        self.topology.clear();
+        self.topology.add_node(self.id, NodeType::Client);
+        for neighbour in self.packet_send.keys() {
+            self.topology.add_node(*neighbour, NodeType::Drone);
+            self.topology.add_edge(self.id, *neighbour);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use wg_2024::controller::*;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+/// Errors returned while encoding or decoding a datagram.
+pub enum CodecError {
+    Truncated,
+    UnknownTag(&'static str, u8),
+    TrailingBytes(usize),
+    TooLong(&'static str, usize), // Length doesn't fit its u16 count
+}
+impl Debug for CodecError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            CodecError::Truncated => write!(f, "Truncated datagram"),
+            CodecError::UnknownTag(what, tag) => write!(f, "Unknown {} tag [{}]", what, tag),
+            CodecError::TrailingBytes(n) => write!(f, "Datagram has {} trailing bytes", n),
+            CodecError::TooLong(what, n) => write!(f, "Too many {} to encode [{}]", what, n),
+        }
+    }
+}
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+
+/// Commands that can travel over the wire. Mirrors `DroneCommand`, but `AddSender` carries
+/// the neighbour's socket address instead of a channel.
+#[derive(Debug, Clone)]
+pub enum RemoteCommand {
+    AddSender(NodeId, SocketAddr),
+    RemoveSender(NodeId),
+    SetPacketDropRate(f32),
+    Crash,
+}
+
+/// Everything a process can receive on its socket.
+#[derive(Debug, Clone)]
+pub enum Frame {
+    Packet(Packet),
+    Event(NodeId, DroneEvent), // Drone that emitted the event
+    Command(RemoteCommand),
+}
+
+/// Encodes a frame, layout is little endian and documented next to every writer.
+pub fn encode_frame(frame: &Frame) -> Result<Vec<u8>, CodecError> {
+    let mut out = Vec::with_capacity(64);
+    match frame {
+        Frame::Packet(packet) => {
+            out.push(0);
+            write_packet(&mut out, packet)?;
+        }
+        Frame::Event(id, event) => {
+            out.push(1);
+            out.push(*id);
+            let (tag, packet) = match event {
+                DroneEvent::PacketSent(p) => (0, p),
+                DroneEvent::PacketDropped(p) => (1, p),
+                DroneEvent::ControllerShortcut(p) => (2, p),
+            };
+            out.push(tag);
+            write_packet(&mut out, packet)?;
+        }
+        Frame::Command(command) => {
+            out.push(2);
+            match command {
+                RemoteCommand::AddSender(id, addr) => {
+                    out.push(0);
+                    out.push(*id);
+                    write_addr(&mut out, addr);
+                }
+                RemoteCommand::RemoveSender(id) => {
+                    out.push(1);
+                    out.push(*id);
+                }
+                RemoteCommand::SetPacketDropRate(pdr) => {
+                    out.push(2);
+                    out.extend_from_slice(&pdr.to_le_bytes());
+                }
+                RemoteCommand::Crash => out.push(3),
+            }
+        }
+    }
+    Ok(out)
+}
+pub fn decode_frame(bytes: &[u8]) -> Result<Frame, CodecError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let frame = match reader.u8()? {
+        0 => Frame::Packet(reader.packet()?),
+        1 => {
+            let id = reader.u8()?;
+            let event = match reader.u8()? {
+                0 => DroneEvent::PacketSent(reader.packet()?),
+                1 => DroneEvent::PacketDropped(reader.packet()?),
+                2 => DroneEvent::ControllerShortcut(reader.packet()?),
+                tag => return Err(CodecError::UnknownTag("event", tag)),
+            };
+            Frame::Event(id, event)
+        }
+        2 => Frame::Command(match reader.u8()? {
+            0 => RemoteCommand::AddSender(reader.u8()?, reader.addr()?),
+            1 => RemoteCommand::RemoveSender(reader.u8()?),
+            2 => RemoteCommand::SetPacketDropRate(f32::from_le_bytes(reader.array()?)),
+            3 => RemoteCommand::Crash,
+            tag => return Err(CodecError::UnknownTag("command", tag)),
+        }),
+        tag => return Err(CodecError::UnknownTag("frame", tag)),
+    };
+    reader.finish()?;
+    Ok(frame)
+}
+/// Shortcuts for the common case of a bare packet.
+pub fn encode_packet(packet: &Packet) -> Result<Vec<u8>, CodecError> {
+    let mut out = Vec::with_capacity(64);
+    write_packet(&mut out, packet)?;
+    Ok(out)
+}
+pub fn decode_packet(bytes: &[u8]) -> Result<Packet, CodecError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let packet = reader.packet()?;
+    reader.finish()?;
+    Ok(packet)
+}
+
+// session_id u64 | hop_index u64 | hops: u16 count + u8 each | pack_type tag u8 + body
+fn write_packet(out: &mut Vec<u8>, packet: &Packet) -> Result<(), CodecError> {
+    out.extend_from_slice(&packet.session_id.to_le_bytes());
+    out.extend_from_slice(&(packet.routing_header.hop_index as u64).to_le_bytes());
+    write_len(out, "hops", packet.routing_header.hops.len())?;
+    out.extend_from_slice(&packet.routing_header.hops);
+    match &packet.pack_type {
+        PacketType::MsgFragment(fragment) => {
+            // index u64 | total u64 | length u8 | data [u8; FRAGMENT_DSIZE]
+            out.push(0);
+            out.extend_from_slice(&fragment.fragment_index.to_le_bytes());
+            out.extend_from_slice(&fragment.total_n_fragments.to_le_bytes());
+            out.push(fragment.length);
+            out.extend_from_slice(&fragment.data);
+        }
+        PacketType::Ack(ack) => {
+            out.push(1);
+            out.extend_from_slice(&ack.fragment_index.to_le_bytes());
+        }
+        PacketType::Nack(nack) => {
+            // index u64 | nack tag u8 | node u8 (only for the variants carrying one)
+            out.push(2);
+            out.extend_from_slice(&nack.fragment_index.to_le_bytes());
+            match nack.nack_type {
+                NackType::ErrorInRouting(id) => out.extend_from_slice(&[0, id]),
+                NackType::DestinationIsDrone => out.push(1),
+                NackType::Dropped => out.push(2),
+                NackType::UnexpectedRecipient(id) => out.extend_from_slice(&[3, id]),
+            }
+        }
+        PacketType::FloodRequest(flood) => {
+            // flood_id u64 | initiator u8 | path_trace
+            out.push(3);
+            out.extend_from_slice(&flood.flood_id.to_le_bytes());
+            out.push(flood.initiator_id);
+            write_path_trace(out, &flood.path_trace)?;
+        }
+        PacketType::FloodResponse(flood) => {
+            // flood_id u64 | path_trace
+            out.push(4);
+            out.extend_from_slice(&flood.flood_id.to_le_bytes());
+            write_path_trace(out, &flood.path_trace)?;
+        }
+    }
+    Ok(())
+}
+// u16 count | (id u8, node type u8) each
+fn write_path_trace(
+    out: &mut Vec<u8>,
+    path_trace: &[(NodeId, NodeType)],
+) -> Result<(), CodecError> {
+    write_len(out, "path trace entries", path_trace.len())?;
+    for (id, node_type) in path_trace {
+        out.push(*id);
+        out.push(match node_type {
+            NodeType::Client => 0,
+            NodeType::Drone => 1,
+            NodeType::Server => 2,
+        });
+    }
+    Ok(())
+}
+fn write_len(out: &mut Vec<u8>, what: &'static str, len: usize) -> Result<(), CodecError> {
+    let len = u16::try_from(len).map_err(|_| CodecError::TooLong(what, len))?;
+    out.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+// family u8 (4 or 6) | 4 or 16 address bytes | port u16
+fn write_addr(out: &mut Vec<u8>, addr: &SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            out.push(4);
+            out.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            out.push(6);
+            out.extend_from_slice(&ip.octets());
+        }
+    }
+    out.extend_from_slice(&addr.port().to_le_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], CodecError> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or(CodecError::Truncated)?;
+        self.pos += n;
+        Ok(slice)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CodecError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+    fn u8(&mut self) -> Result<u8, CodecError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, CodecError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    fn u64(&mut self) -> Result<u64, CodecError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    fn addr(&mut self) -> Result<SocketAddr, CodecError> {
+        let ip = match self.u8()? {
+            4 => IpAddr::V4(Ipv4Addr::from(self.array::<4>()?)),
+            6 => IpAddr::V6(Ipv6Addr::from(self.array::<16>()?)),
+            tag => return Err(CodecError::UnknownTag("address family", tag)),
+        };
+        Ok(SocketAddr::new(ip, self.u16()?))
+    }
+    fn finish(&self) -> Result<(), CodecError> {
+        match self.bytes.len() - self.pos {
+            0 => Ok(()),
+            n => Err(CodecError::TrailingBytes(n)),
+        }
+    }
+    fn packet(&mut self) -> Result<Packet, CodecError> {
+        let session_id = self.u64()?;
+        let hop_index = self.u64()? as usize;
+        let hops_len = self.u16()? as usize;
+        let hops = self.take(hops_len)?.to_vec();
+        let pack_type = match self.u8()? {
+            0 => PacketType::MsgFragment(Fragment {
+                fragment_index: self.u64()?,
+                total_n_fragments: self.u64()?,
+                length: self.u8()?,
+                data: self.array()?,
+            }),
+            1 => PacketType::Ack(Ack {
+                fragment_index: self.u64()?,
+            }),
+            2 => PacketType::Nack(Nack {
+                fragment_index: self.u64()?,
+                nack_type: match self.u8()? {
+                    0 => NackType::ErrorInRouting(self.u8()?),
+                    1 => NackType::DestinationIsDrone,
+                    2 => NackType::Dropped,
+                    3 => NackType::UnexpectedRecipient(self.u8()?),
+                    tag => return Err(CodecError::UnknownTag("nack", tag)),
+                },
+            }),
+            3 => PacketType::FloodRequest(FloodRequest {
+                flood_id: self.u64()?,
+                initiator_id: self.u8()?,
+                path_trace: self.path_trace()?,
+            }),
+            4 => PacketType::FloodResponse(FloodResponse {
+                flood_id: self.u64()?,
+                path_trace: self.path_trace()?,
+            }),
+            tag => return Err(CodecError::UnknownTag("packet type", tag)),
+        };
+        Ok(Packet {
+            session_id,
+            routing_header: SourceRoutingHeader { hop_index, hops },
+            pack_type,
+        })
+    }
+    fn path_trace(&mut self) -> Result<Vec<(NodeId, NodeType)>, CodecError> {
+        let len = self.u16()? as usize;
+        let mut path_trace = Vec::with_capacity(len);
+        for _ in 0..len {
+            let id = self.u8()?;
+            let node_type = match self.u8()? {
+                0 => NodeType::Client,
+                1 => NodeType::Drone,
+                2 => NodeType::Server,
+                tag => return Err(CodecError::UnknownTag("node type", tag)),
+            };
+            path_trace.push((id, node_type));
+        }
+        Ok(path_trace)
+    }
+}
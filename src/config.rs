@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+
+/// Topology file, same shape as the WGL network initializer config but in JSON.
+/// ```json
+/// { "drone": [{ "id": 1, "connected_node_ids": [2, 10], "pdr": 0.1 }],
+///   "client": [{ "id": 10, "connected_drone_ids": [1] }],
+///   "server": [{ "id": 20, "connected_drone_ids": [2] }] }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub drone: Vec<DroneEntry>,
+    #[serde(default)]
+    pub client: Vec<EdgeEntry>,
+    #[serde(default)]
+    pub server: Vec<EdgeEntry>,
+    #[serde(default = "default_base_port")]
+    pub base_port: u16, // Node N listens on 127.0.0.1:(base_port + N) when using the UDP transport
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DroneEntry {
+    pub id: NodeId,
+    pub connected_node_ids: Vec<NodeId>,
+    pub pdr: f32,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EdgeEntry {
+    pub id: NodeId,
+    pub connected_drone_ids: Vec<NodeId>,
+}
+fn default_base_port() -> u16 {
+    47000
+}
+
+impl NetworkConfig {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|er| format!("{}: {}", path, er))?;
+        let config: Self = serde_json::from_str(&text).map_err(|er| format!("{}: {}", path, er))?;
+        // Room for every node id and the controller above it
+        if config.base_port > u16::MAX - 256 {
+            return Err(format!(
+                "{}: base_port {} is above {}",
+                path,
+                config.base_port,
+                u16::MAX - 256
+            ));
+        }
+        Ok(config)
+    }
+    pub fn node_type(&self, id: NodeId) -> Option<NodeType> {
+        if self.drone.iter().any(|d| d.id == id) {
+            Some(NodeType::Drone)
+        } else if self.client.iter().any(|c| c.id == id) {
+            Some(NodeType::Client)
+        } else if self.server.iter().any(|s| s.id == id) {
+            Some(NodeType::Server)
+        } else {
+            None
+        }
+    }
+    pub fn pdr_of(&self, id: NodeId) -> Option<f32> {
+        self.drone.iter().find(|d| d.id == id).map(|d| d.pdr)
+    }
+    /// Links may be listed on one side only, so we look at both ends.
+    pub fn neighbours_of(&self, id: NodeId) -> Vec<NodeId> {
+        let mut neighbours = Vec::new();
+        let mut add = |n: NodeId| {
+            if n != id && !neighbours.contains(&n) {
+                neighbours.push(n);
+            }
+        };
+        for d in &self.drone {
+            if d.id == id {
+                d.connected_node_ids.iter().for_each(|n| add(*n));
+            } else if d.connected_node_ids.contains(&id) {
+                add(d.id);
+            }
+        }
+        for e in self.client.iter().chain(self.server.iter()) {
+            if e.id == id {
+                e.connected_drone_ids.iter().for_each(|n| add(*n));
+            } else if e.connected_drone_ids.contains(&id) {
+                add(e.id);
+            }
+        }
+        neighbours
+    }
+    /// `from_file` makes sure the port fits, configs built by hand have to keep base_port low.
+    pub fn address_of(&self, id: NodeId) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.base_port + id as u16))
+    }
+    // Node ids are u8, so this never collides with a node address.
+    pub fn controller_address(&self) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.base_port + 256))
+    }
+}
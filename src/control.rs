@@ -0,0 +1,61 @@
+use crate::builder::LossModel;
+use crate::snapshot::DroneSnapshot;
+use crate::status::{DroneState, DroneStatus};
+use crossbeam_channel::{bounded, Sender};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Drone settings `DroneCommand` doesn't cover, sent on the channel from `Drone::control_channel`.
+/// The drone handles them after the `DroneCommand`s and before the packets.
+pub enum ControlCommand {
+    SetLogging(bool),
+    SetLogLevel(LogLevel),
+    ResetStats,        // Counters back to 0
+    ClearFloodHistory, // Floods already seen will be forwarded again
+    SetLossModel(LossModel),
+    QueryState(Sender<DroneStatus>), // The drone replies once, see `query_state`
+    DumpState(Sender<DroneState>),   // Same with everything the drone knows, see `dump_state`
+    Snapshot(Sender<DroneSnapshot>), // What restoring the drone takes, see `snapshot`
+}
+
+/// Info only logs what happens to packets (drops, discards, send errors),
+/// Debug also logs every step of their handling.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub enum LogLevel {
+    Info,
+    #[default]
+    Debug,
+}
+
+/// Asks a drone for its state, waiting at most `timeout` for the reply.
+pub fn query_state(
+    control: &Sender<ControlCommand>,
+    timeout: Duration,
+) -> Result<DroneStatus, String> {
+    request(control, ControlCommand::QueryState, timeout)
+}
+/// Asks a drone for its full state. A drone blocked on a full neighbour queue can't answer,
+/// its last published `DroneStatus` is then the best we have.
+pub fn dump_state(
+    control: &Sender<ControlCommand>,
+    timeout: Duration,
+) -> Result<DroneState, String> {
+    request(control, ControlCommand::DumpState, timeout)
+}
+pub fn snapshot(
+    control: &Sender<ControlCommand>,
+    timeout: Duration,
+) -> Result<DroneSnapshot, String> {
+    request(control, ControlCommand::Snapshot, timeout)
+}
+fn request<T>(
+    control: &Sender<ControlCommand>,
+    command: fn(Sender<T>) -> ControlCommand,
+    timeout: Duration,
+) -> Result<T, String> {
+    let (reply_send, reply_recv) = bounded(1);
+    (control.send(command(reply_send))).map_err(|er| er.to_string())?;
+    reply_recv
+        .recv_timeout(timeout)
+        .map_err(|er| er.to_string())
+}
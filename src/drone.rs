@@ -1,7 +1,18 @@
-use crossbeam_channel::{Receiver, Sender};
-use rand::{rng, Rng};
+use crate::builder::{
+    CrashedFloodPolicy, DroneConfig, ExcessFloodPolicy, FloodDedup, LossModel, Profile, Scheduling,
+};
+use crate::control::{ControlCommand, LogLevel};
+use crate::pipeline::{HookAction, HookContext, PacketHook, Pipeline};
+use crate::scheduler::Scheduler;
+use crate::snapshot::DroneSnapshot;
+use crate::status::{DroneCounters, DroneState, DroneStatus, FloodRecord, StatusBoard};
+use crossbeam_channel::{after, at, never, unbounded, Receiver, Sender};
+use rand::{rng, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
+use std::time::{Duration, Instant};
 use wg_2024::controller::*;
 use wg_2024::network::*;
 use wg_2024::packet::NackType::{Dropped, ErrorInRouting, UnexpectedRecipient};
@@ -12,6 +23,7 @@ enum SendingCodes {
     SuccessfullySent(u64),
     ErrorSending(String),
     NoNextHop(String),
+    HookDropped,
 }
 impl Debug for SendingCodes {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
@@ -19,6 +31,7 @@ impl Debug for SendingCodes {
             SendingCodes::SuccessfullySent(n) => write!(f, "Successfully sent [Packet {}]", n),
             SendingCodes::ErrorSending(s) => write!(f, "Error sending the packet [{}]", s),
             SendingCodes::NoNextHop(s) => write!(f, "No next hop available [{}]", s),
+            SendingCodes::HookDropped => write!(f, "Dropped by a hook"),
         }
     }
 }
@@ -41,11 +54,47 @@ enum RoutingCodes {
     DestinationArrived,
     HopsMismatch,
 }
+/// Why validate_route refuses to forward a packet, with the node to blame in the Nack.
+enum RouteError {
+    Revisits(NodeId),    // The route goes through this node twice
+    BouncesBack(NodeId), // Next hop is the node the packet just came from
+    NotNeighbour(NodeId),
+}
+impl Display for RouteError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            RouteError::Revisits(n) => write!(f, "route visits {} more than once", n),
+            RouteError::BouncesBack(n) => write!(f, "route goes back to {}", n),
+            RouteError::NotNeighbour(n) => write!(f, "{} is not a neighbour", n),
+        }
+    }
+}
+/// Packets only update the published status this often, a Mutex and a Vec per packet add up.
+const STATUS_INTERVAL: Duration = Duration::from_millis(50);
+/// Packets drained into the internal queue at most, without a `queue_size`.
+const MAX_QUEUED: usize = 1024;
+/// Flood tokens of one initiator, see FloodRateLimit.
+struct TokenBucket {
+    tokens: f64,
+    refilled: Instant,
+}
 /// Drone extra-field, uses it to store data.
 struct Cache {
-    history_floodreq: HashMap<NodeId, Vec<u64>>,
+    history_floodreq: HashMap<NodeId, Vec<FloodRecord>>,
     crashed: bool,
     logging_enabled: bool,
+    status_board: Option<StatusBoard>, // Where we publish our status, if someone's watching
+    status_published: Instant,         // Last publish_status
+    status_due: Option<Instant>,       // When the changes made by packets get published
+    counters: Cell<DroneCounters>,     // Cell as sending only borrows the drone
+    config: DroneConfig,               // See DroneBuilder, defaults with the trait constructor
+    rng: Option<ChaCha12Rng>,          // Seeded from config.seed, thread rng otherwise
+    in_burst: bool,                    // State of LossModel::Burst
+    log_level: LogLevel,
+    control: Option<Receiver<ControlCommand>>, // See control_channel
+    pipeline: RefCell<Pipeline>,               // RefCell for the same reason as counters
+    flood_buckets: HashMap<NodeId, TokenBucket>, // Per initiator, with config.flood_rate_limit
+    scheduler: Scheduler, // Packets drained from packet_recv, unless config.scheduling is Fifo
 }
 /// Last Update: 08/02/25, Status: No known issue atm
 pub struct Drone {
@@ -78,37 +127,108 @@ impl wg_2024::drone::Drone for Drone {
                 history_floodreq: HashMap::new(),
                 crashed: false,
                 logging_enabled: true,
+                status_board: None,
+                status_published: Instant::now(),
+                status_due: None,
+                counters: Cell::new(DroneCounters::default()),
+                config: DroneConfig::default(),
+                rng: None,
+                in_burst: false,
+                log_level: LogLevel::Debug,
+                control: None,
+                pipeline: RefCell::new(Pipeline::new()),
+                flood_buckets: HashMap::new(),
+                scheduler: Scheduler::new(),
             },
         }
     }
     fn run(&mut self) {
+        // Nobody asked for a control channel -> we wait on one that never delivers.
+        let mut control = self.cache.control.clone().unwrap_or_else(never);
         loop {
+            let mut control_closed = false;
+            // Fires right away while the internal queue has packets, after the channels
+            let queued = if self.cache.scheduler.is_empty() {
+                never()
+            } else {
+                after(Duration::ZERO)
+            };
+            let status_due = self.cache.status_due.map(at).unwrap_or_else(never);
             // Listen for packets and commands
             crossbeam_channel::select_biased! { // Prioritizing Controller messages using select_biased! macro.
                 recv(self.controller_recv) -> command => {
                     if let Ok(command) = command {
                         self.handle_command(command);
+                        self.publish_status();
                     }else{
                         // It means that channel has been closed -> We gotta shut off drone run method
                         return;
                     }
                 }
+                recv(control) -> command => {
+                    if let Ok(command) = command {
+                        self.handle_control(command);
+                        self.publish_status();
+                    }else{
+                        // Our own channel, the drone keeps running without it
+                        control_closed = true;
+                    }
+                }
                 recv(self.packet_recv) -> packet => {
                     if let Ok(packet) = packet{
-                        self.drone_behaviour(packet);
+                        if self.cache.config.scheduling == Scheduling::Fifo {
+                            self.drone_behaviour(packet);
+                        } else {
+                            self.cache.scheduler.push(packet);
+                            // Bounded, the rest waits in the channel and its senders feel it
+                            let limit = self.cache.config.queue_size.unwrap_or(MAX_QUEUED);
+                            while self.cache.scheduler.len() < limit {
+                                let Ok(packet) = self.packet_recv.try_recv() else {
+                                    break;
+                                };
+                                self.cache.scheduler.push(packet);
+                            }
+                            self.handle_queued();
+                        }
+                        self.status_changed();
                     }else{
                         // It means that channel has been closed -> We gotta shut off drone run metho
+                        while !self.cache.scheduler.is_empty() {
+                            self.handle_queued();
+                        }
+                        self.publish_status();
                         return;
                     }
                 }
+                recv(queued) -> _ => {
+                    self.handle_queued();
+                    self.status_changed();
+                }
+                recv(status_due) -> _ => {
+                    self.publish_status();
+                }
+            }
+            if control_closed {
+                control = never();
             }
         }
     }
 }
 impl Drone {
+    fn handle_queued(&mut self) {
+        if let Some((_, packet)) = self.cache.scheduler.pop(self.cache.config.scheduling) {
+            self.drone_behaviour(packet);
+        }
+    }
     fn log<S: AsRef<str>>(&self, message: S) {
+        self.log_at(LogLevel::Debug, message);
+    }
+    fn log_info<S: AsRef<str>>(&self, message: S) {
+        self.log_at(LogLevel::Info, message);
+    }
+    fn log_at<S: AsRef<str>>(&self, level: LogLevel, message: S) {
         #[cfg(feature = "log")]
-        if self.cache.logging_enabled {
+        if self.cache.logging_enabled && level <= self.cache.log_level {
             println!("LeDron ID {} - {}", self.id, message.as_ref());
         }
     }
@@ -116,31 +236,127 @@ impl Drone {
         // Self-Explanatory
         self.cache.logging_enabled = log
     }
-    fn drone_behaviour(&mut self, packet: Packet) {
+    pub fn configure(&mut self, config: DroneConfig) {
+        // Usually through DroneBuilder, which validates the config first
+        self.cache.logging_enabled = config.logging;
+        self.cache.rng =
+            (config.seed).map(|seed| ChaCha12Rng::seed_from_u64(seed ^ self.id as u64));
+        self.cache.config = config;
+        self.cache.flood_buckets.clear(); // The limit may have changed
+    }
+    /// Channel for the `ControlCommand`s, a new call replaces the previous channel.
+    /// Must be called before `run`.
+    pub fn control_channel(&mut self) -> Sender<ControlCommand> {
+        let (control_send, control_recv) = unbounded();
+        self.cache.control = Some(control_recv);
+        control_send
+    }
+    /// Runs after the hooks already added, see PacketHook.
+    pub fn add_hook(&mut self, hook: Box<dyn PacketHook>) {
+        self.cache.pipeline.get_mut().push(hook);
+    }
+    fn hook_context(&self) -> HookContext {
+        HookContext {
+            drone: self.id,
+            pdr: self.pdr,
+            crashed: self.cache.crashed,
+        }
+    }
+    pub fn status_board(&mut self, board: StatusBoard) {
+        // Published after every command, and at most every STATUS_INTERVAL for packets
+        self.cache.status_board = Some(board);
+        self.publish_status();
+    }
+    fn publish_status(&mut self) {
+        if let Some(board) = &self.cache.status_board {
+            board.publish(self.id, self.status());
+        }
+        self.cache.status_published = Instant::now();
+        self.cache.status_due = None;
+    }
+    /// After a packet: publishes now if the last status is old enough, or schedules it.
+    fn status_changed(&mut self) {
+        if self.cache.status_board.is_none() || self.cache.status_due.is_some() {
+            return;
+        }
+        let due = self.cache.status_published + STATUS_INTERVAL;
+        if Instant::now() >= due {
+            self.publish_status();
+        } else {
+            self.cache.status_due = Some(due);
+        }
+    }
+    pub fn status(&self) -> DroneStatus {
+        let mut neighbours: Vec<NodeId> = self.packet_send.keys().copied().collect();
+        neighbours.sort();
+        DroneStatus {
+            pdr: self.pdr,
+            crashed: self.cache.crashed,
+            neighbours,
+            flood_history: self.cache.history_floodreq.values().map(Vec::len).sum(),
+            queue_depth: self.packet_recv.len() + self.cache.scheduler.len(),
+            counters: self.cache.counters.get(),
+            queue_delays: self.cache.scheduler.delays(),
+        }
+    }
+    pub fn counters(&self) -> DroneCounters {
+        self.cache.counters.get()
+    }
+    fn count(&self, update: impl FnOnce(&mut DroneCounters)) {
+        let mut counters = self.cache.counters.get();
+        update(&mut counters);
+        self.cache.counters.set(counters);
+    }
+    fn drone_behaviour(&mut self, mut packet: Packet) {
+        let context = self.hook_context();
+        match (self.cache.pipeline.get_mut()).before_routing(&context, &mut packet) {
+            HookAction::Continue => {}
+            HookAction::Drop => {
+                self.log_info("Packet dropped by a hook");
+                return;
+            }
+            HookAction::Redirect(target) => {
+                self.log_info(format!("{:?}", self.redirect(target, packet)));
+                return;
+            }
+        }
         if self.cache.crashed {
             // We gotta empty the queue, only Ack, Nack, FloodResponse already sent
             // before the drone-crash will be forwarded during a crashing status.
-            match packet.clone().pack_type {
+            match &packet.pack_type {
                 PacketType::Nack(_) | PacketType::Ack(_) | PacketType::FloodResponse(_) => {
                     self.handle_packet(packet);
                 }
-                PacketType::FloodRequest(_) => {
-                    self.log("Dropping packet...");
-                    self.send_packet(
-                        Self::build_packet_nack(packet, ErrorInRouting(self.id), None),
-                        None,
-                    );
-                }
+                PacketType::FloodRequest(flood) => match self.cache.config.crashed_floods {
+                    // A Nack would follow the flood's routing header, which means nothing.
+                    CrashedFloodPolicy::Drop => {
+                        self.log_info("Crashed, dropping FloodRequest...");
+                        let _ = self.sendto_controller(packet, ControllerTypes::Dropped);
+                    }
+                    CrashedFloodPolicy::Respond => {
+                        self.log_info("Crashed, answering FloodRequest...");
+                        let mut flood = flood.clone();
+                        flood.path_trace.push((self.id, NodeType::Drone));
+                        let response = Self::build_packet_flood_response(flood, packet.session_id);
+                        // Shortcut through the controller if whoever sent it isn't linked to us anymore
+                        if let SendingCodes::NoNextHop(_) = self.send_packet(response, None) {
+                            self.log_info("FloodResponse handed to the controller.");
+                        }
+                    }
+                },
                 PacketType::MsgFragment(fragment_id) => {
-                    self.log("Dropping packet...");
-                    self.send_packet(
-                        Self::build_packet_nack(
-                            packet,
-                            ErrorInRouting(self.id),
-                            Some(fragment_id.fragment_index),
-                        ),
-                        None,
+                    self.log_info("Dropping packet...");
+                    let nack = Self::build_packet_nack(
+                        &packet,
+                        ErrorInRouting(self.id),
+                        Some(fragment_id.fragment_index),
                     );
+                    if packet.routing_header.valid_hop_index() {
+                        self.send_nack(nack);
+                    } else {
+                        // We can't tell where it came from, only the controller can be told
+                        self.nack_to_controller(nack);
+                    }
                 }
             }
         } else {
@@ -151,34 +367,35 @@ impl Drone {
         self.log("Handling packet...");
         match self.handle_routing_header(&packet.routing_header) {
             RoutingCodes::Correct => {
+                if let Err(error) = self.validate_route(&packet) {
+                    self.reject_route(packet, error);
+                    return;
+                }
                 let return_packet: Packet;
-                match packet.pack_type.clone() {
+                match &packet.pack_type {
                     PacketType::MsgFragment(fragment_id) => {
                         self.log("Handling fragment...");
                         // We consider our PDR, if bool throws true packet gets dropped.
-                        let mut rng = rng();
-                        if rng.random_bool(self.pdr as f64) {
+                        if self.should_drop() {
                             // Drop
-                            self.log("Dropping packet...");
-                            let _ =
-                                self.sendto_controller(packet.clone(), ControllerTypes::Dropped); // We send the packet to Sim.Controller
-                            self.send_packet(
-                                Self::build_packet_nack(
-                                    packet,
-                                    Dropped,
-                                    Some(fragment_id.fragment_index),
-                                ),
-                                None,
+                            self.log_info("Dropping packet...");
+                            self.count(|c| c.dropped += 1);
+                            let nack = Self::build_packet_nack(
+                                &packet,
+                                Dropped,
+                                Some(fragment_id.fragment_index),
                             );
+                            let _ = self.sendto_controller(packet, ControllerTypes::Dropped); // We send the packet to Sim.Controller
+                            self.send_nack(nack);
                         } else {
                             // println!("Drone ID {} - NOT dropping packet...", self.id);
-                            return_packet = self.update_packet_to_forward(packet);
+                            return_packet = Self::update_packet_to_forward(packet);
                             self.send_packet(return_packet, None);
                         }
                     }
                     PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => {
                         // Send the packet following the SRH
-                        return_packet = self.update_packet_to_forward(packet);
+                        return_packet = Self::update_packet_to_forward(packet);
                         self.send_packet(return_packet, None);
                     }
                     _ => {
@@ -191,94 +408,81 @@ impl Drone {
                 // Check if it's an FloodReq, as handle_routing_header doesn't check it.
                 // Gotta implement shortcut to Simulation Controller
                 self.log("Packet with Drone destination arrived...");
-                match packet.pack_type.clone() {
-                    PacketType::FloodRequest(packet_id) => {
-                        self.handle_flooding_req(packet_id, packet);
+                match &packet.pack_type {
+                    PacketType::FloodRequest(_) => {
+                        self.handle_flooding_req(packet);
                     }
                     PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => {
-                        let _ = self.sendto_controller(packet.clone(), ControllerTypes::Shortcut);
-                        self.log(
-                            format!(
-                                "{:?}",
-                                self.send_packet(
-                                    Self::build_packet_nack(
-                                        packet,
-                                        NackType::DestinationIsDrone,
-                                        None
-                                    ),
-                                    None
-                                )
-                            )
-                            .as_str(),
-                        );
+                        let nack =
+                            Self::build_packet_nack(&packet, NackType::DestinationIsDrone, None);
+                        let _ = self.sendto_controller(packet, ControllerTypes::Shortcut);
+                        self.log(format!("{:?}", self.send_nack(nack)).as_str());
                     }
                     _ => {
                         // DestinationIsDrone
-                        self.log(
-                            format!(
-                                "{:?}",
-                                self.send_packet(
-                                    Self::build_packet_nack(
-                                        packet,
-                                        NackType::DestinationIsDrone,
-                                        None
-                                    ),
-                                    None
-                                )
-                            )
-                            .as_str(),
-                        );
+                        let nack =
+                            Self::build_packet_nack(&packet, NackType::DestinationIsDrone, None);
+                        self.log(format!("{:?}", self.send_nack(nack)).as_str());
                     }
                 }
             }
             RoutingCodes::HopsMismatch => {
                 // Checks if it's a FloodReq, if not, send UnexpectedRecipient back.
-                match packet.pack_type.clone() {
+                match &packet.pack_type {
                     // If the packet is of Fragment type we have to indicate the index number too.
-                    PacketType::FloodRequest(packet_id) => {
-                        self.handle_flooding_req(packet_id, packet);
+                    PacketType::FloodRequest(_) => {
+                        self.handle_flooding_req(packet);
                     }
                     PacketType::MsgFragment(fragment_id) => {
+                        let fragment_index = fragment_id.fragment_index;
                         if packet.routing_header.valid_hop_index() {
-                            self.send_packet(
-                                Self::build_packet_nack(
-                                    packet.clone(),
-                                    UnexpectedRecipient(
-                                        packet.routing_header.hops[packet.routing_header.hop_index],
-                                    ),
-                                    Some(fragment_id.fragment_index),
+                            self.send_nack(Self::build_packet_nack(
+                                &packet,
+                                UnexpectedRecipient(
+                                    packet.routing_header.hops[packet.routing_header.hop_index],
                                 ),
-                                None,
-                            );
+                                Some(fragment_index),
+                            ));
+                        } else if let Some(hop_index) = self.infer_hop_index(&packet.routing_header)
+                        {
+                            // Hop index is out of bounds but we know where we are in the route: Nack back from there.
+                            self.log_info(format!(
+                                "Recovered hop index {} of packet [SESSION ID: {:?}] (OUB)",
+                                hop_index, packet.session_id
+                            ));
+                            let mut recovered = packet;
+                            recovered.routing_header.hop_index = hop_index;
+                            self.send_nack(Self::build_packet_nack(
+                                &recovered,
+                                UnexpectedRecipient(self.id),
+                                Some(fragment_index),
+                            ));
                         } else {
-                            // SRH Received is not valid, I can't send back a Nack as I might have to guess where it did come from, fuck the drone before :(
-                            self.log(format!("Discarding packet [SESSION ID: {:?}] because it has an unknown SRH (OUB)", packet.session_id));
-                            let _ = self.sendto_controller(
-                                Self::build_packet_nack(
-                                    packet.clone(),
-                                    UnexpectedRecipient(self.id),
-                                    None,
-                                ),
-                                ControllerTypes::Dropped,
-                            ); // As we asked the WGC what to do in this case, we just got told to send to controller an UnexpectedRecipient Nack with the drone self.id.
+                            // SRH Received is not valid and we can't tell where it did come from, only the controller can be told.
+                            self.log_info(format!("Discarding packet [SESSION ID: {:?}] because it has an unknown SRH (OUB)", packet.session_id));
+                            self.nack_to_controller(Packet {
+                                session_id: packet.session_id,
+                                routing_header: packet.routing_header, // As received, the Nack can't be routed anyway
+                                pack_type: PacketType::Nack(Nack {
+                                    fragment_index,
+                                    nack_type: UnexpectedRecipient(self.id),
+                                }),
+                            }); // As we asked the WGC what to do in this case, we just got told to send to controller an UnexpectedRecipient Nack with the drone self.id.
                         }
                     }
                     PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => {
                         if packet.routing_header.valid_hop_index() {
-                            self.send_packet(
-                                Self::build_packet_nack(
-                                    packet.clone(),
-                                    UnexpectedRecipient(
-                                        packet.routing_header.hops[packet.routing_header.hop_index],
-                                    ),
-                                    None,
+                            self.send_nack(Self::build_packet_nack(
+                                &packet,
+                                UnexpectedRecipient(
+                                    packet.routing_header.hops[packet.routing_header.hop_index],
                                 ),
                                 None,
-                            );
+                            ));
                             let _ = self.sendto_controller(packet, ControllerTypes::Shortcut);
                         } else {
                             // SRH Received is not valid, I can't send back a Nack as I might have to guess where it did come from, fuck the drone before :(
-                            self.log(format!("Discarding packet [SESSION ID: {:?}] because it has an unknown SRH (OUB)", packet.session_id));
+                            self.log_info(format!("Discarding packet [SESSION ID: {:?}] because it has an unknown SRH (OUB)", packet.session_id));
                             let _ = self.sendto_controller(packet, ControllerTypes::Shortcut);
                             //let _ = self.sendto_controller(Self::build_packet_nack(packet.clone(), UnexpectedRecipient(self.id), None), false); // As we asked the WGC what to do in this case, we just got told to send to controller an UnexpectedRecipient Nack with the drone self.id.
                         }
@@ -287,12 +491,80 @@ impl Drone {
             }
         }
     }
+    pub fn state(&self) -> DroneState {
+        let mut neighbours: Vec<NodeId> = self.packet_send.keys().copied().collect();
+        neighbours.sort();
+        DroneState {
+            id: self.id,
+            pdr: self.pdr,
+            crashed: self.cache.crashed,
+            neighbours,
+            flood_history: (self.cache.history_floodreq.iter())
+                .map(|(initiator, floods)| (*initiator, floods.clone()))
+                .collect(),
+            counters: self.cache.counters.get(),
+            queue_depth: self.packet_recv.len() + self.cache.scheduler.len(),
+            logging: self.cache.logging_enabled,
+            log_level: self.cache.log_level,
+            config: self.cache.config.clone(),
+            queue_delays: self.cache.scheduler.delays(),
+        }
+    }
+    pub fn snapshot(&self) -> DroneSnapshot {
+        DroneSnapshot {
+            state: self.state(),
+            rng: self.cache.rng.clone(),
+            in_burst: self.cache.in_burst,
+        }
+    }
+    /// Everything but id, PDR, config and channels, which DroneBuilder::from_snapshot sets.
+    pub(crate) fn restore(&mut self, snapshot: DroneSnapshot) {
+        let state = snapshot.state;
+        self.cache.crashed = state.crashed;
+        self.cache.history_floodreq = state.flood_history.into_iter().collect();
+        self.cache.counters.set(state.counters);
+        self.cache.logging_enabled = state.logging;
+        self.cache.log_level = state.log_level;
+        self.cache.rng = snapshot.rng;
+        self.cache.in_burst = snapshot.in_burst;
+    }
+    fn should_drop(&mut self) -> bool {
+        if self.cache.config.profile == Profile::Lossless {
+            return false;
+        }
+        let pdr = match self.cache.config.loss_model {
+            LossModel::Uniform => self.pdr as f64,
+            LossModel::Burst {
+                enter,
+                leave,
+                burst_pdr,
+            } => {
+                let switch = if self.cache.in_burst { leave } else { enter };
+                if self.random_bool(switch) {
+                    self.cache.in_burst = !self.cache.in_burst;
+                }
+                if self.cache.in_burst {
+                    burst_pdr
+                } else {
+                    self.pdr as f64
+                }
+            }
+        };
+        self.random_bool(pdr)
+    }
+    fn random_bool(&mut self, p: f64) -> bool {
+        let p = p.clamp(0.0, 1.0);
+        match &mut self.cache.rng {
+            Some(seeded) => seeded.random_bool(p),
+            None => rng().random_bool(p),
+        }
+    }
     fn handle_command(&mut self, command: DroneCommand) {
         self.log("Handling commands...");
         match command {
             DroneCommand::Crash => {
                 self.cache.crashed = true;
-                self.log("Crashed the drone, Simulation Controller deleting the connection!");
+                self.log_info("Crashed the drone, Simulation Controller deleting the connection!");
             }
             DroneCommand::SetPacketDropRate(newpdr) => {
                 self.pdr = newpdr;
@@ -314,6 +586,97 @@ impl Drone {
         }
     } // Simple function that handles the Simulation Controller commands sent to the drone
 
+    fn handle_control(&mut self, command: ControlCommand) {
+        self.log("Handling control command...");
+        match command {
+            ControlCommand::SetLogging(log) => self.cache.logging_enabled = log,
+            ControlCommand::SetLogLevel(level) => self.cache.log_level = level,
+            ControlCommand::ResetStats => {
+                self.cache.counters.set(DroneCounters::default());
+                self.cache.scheduler.reset_delays();
+            }
+            ControlCommand::ClearFloodHistory => self.cache.history_floodreq.clear(),
+            // A NaN probability would survive random_bool's clamp and panic
+            ControlCommand::SetLossModel(model) => match model.validate() {
+                Ok(()) => {
+                    self.cache.config.loss_model = model;
+                    self.cache.in_burst = false;
+                }
+                Err(er) => self.log_info(format!("Ignoring SetLossModel, {}", er)),
+            },
+            ControlCommand::QueryState(reply) => {
+                // Whoever asked may have given up already
+                let _ = reply.try_send(self.status());
+            }
+            ControlCommand::DumpState(reply) => {
+                let _ = reply.try_send(self.state());
+            }
+            ControlCommand::Snapshot(reply) => {
+                let _ = reply.try_send(self.snapshot());
+            }
+        }
+    }
+
+    // Where we are in a route whose hop index is out of bounds, when there's no doubt:
+    // we appear exactly once, not first, and the hop before us is a neighbour to Nack back to.
+    fn infer_hop_index(&self, srh: &SourceRoutingHeader) -> Option<usize> {
+        let mut positions = (srh.hops.iter())
+            .enumerate()
+            .filter(|(_, hop)| **hop == self.id);
+        let (position, _) = positions.next()?;
+        if positions.next().is_some() || position == 0 {
+            return None;
+        }
+        (self.packet_send.contains_key(&srh.hops[position - 1])).then_some(position)
+    }
+    // Runs once handle_routing_header said we are the current hop and someone is after us.
+    // Empty or out of bounds headers never get here, they are HopsMismatch.
+    // FloodResponses are exempt from the loop checks: a drone answering a flood it already saw
+    // is in the path trace twice, so their route legitimately comes back through it.
+    fn validate_route(&self, packet: &Packet) -> Result<(), RouteError> {
+        let srh = &packet.routing_header;
+        let next_hop = srh.hops[srh.hop_index + 1];
+        if !matches!(packet.pack_type, PacketType::FloodResponse(_)) {
+            if srh.hop_index > 0 && srh.hops[srh.hop_index - 1] == next_hop {
+                return Err(RouteError::BouncesBack(next_hop));
+            }
+            let mut seen = [false; 256];
+            for hop in &srh.hops {
+                if std::mem::replace(&mut seen[*hop as usize], true) {
+                    return Err(RouteError::Revisits(*hop));
+                }
+            }
+        }
+        if !self.packet_send.contains_key(&next_hop) {
+            return Err(RouteError::NotNeighbour(next_hop));
+        }
+        Ok(())
+    }
+    // Fragments are nacked, the other packets can't be so they go to the controller. Neither is
+    // a PDR drop, invalid_routes counts them. A missing link is an ErrorInRouting of the node
+    // we can't reach; a looping route isn't any node's fault, we refuse it as its recipient.
+    fn reject_route(&self, packet: Packet, error: RouteError) {
+        self.log_info(format!(
+            "Rejecting [SESSION ID: {}], {}: {:?}",
+            packet.session_id, error, packet.routing_header.hops
+        ));
+        self.count(|c| c.invalid_routes += 1);
+        let nack_type = match error {
+            RouteError::Revisits(_) | RouteError::BouncesBack(_) => UnexpectedRecipient(self.id),
+            RouteError::NotNeighbour(n) => ErrorInRouting(n),
+        };
+        match &packet.pack_type {
+            PacketType::MsgFragment(fragment) => {
+                let nack =
+                    Self::build_packet_nack(&packet, nack_type, Some(fragment.fragment_index));
+                self.send_nack(nack);
+            }
+            _ => {
+                let _ = self.sendto_controller(packet, ControllerTypes::Shortcut);
+            }
+        }
+    }
+
     // Return - Codes are wrote on the first lines
     fn handle_routing_header(&self, srh: &SourceRoutingHeader) -> RoutingCodes {
         self.log("Handling routing header...");
@@ -333,95 +696,155 @@ impl Drone {
             RoutingCodes::HopsMismatch // UnexpectedRecipient
         }
     }
-    fn handle_flooding_req(&mut self, mut packet_id: FloodRequest, packet: Packet) {
+    fn handle_flooding_req(&mut self, packet: Packet) {
         self.log("Handling FloodRequest...");
-        if let Some(floods_sent) = self.cache.history_floodreq.get_mut(&packet_id.initiator_id) {
-            if floods_sent.contains(&packet_id.flood_id) {
-                // Already received this FloodReq, we need to build a FloodResponse
+        let PacketType::FloodRequest(flood) = &packet.pack_type else {
+            return;
+        };
+        let arrivals = self.flood_arrivals(flood, packet.session_id);
+        // Went through us already: forwarding it again would only make it loop, whatever
+        // FloodDedup::Paths allows or the history forgot.
+        let looped = (flood.path_trace.iter()).any(|(id, _)| *id == self.id);
+        if arrivals == 0 && !looped && !self.take_flood_token(flood.initiator_id) {
+            self.throttle_flood(packet);
+            return;
+        }
+        let forward = !looped
+            && match self.cache.config.flood_dedup {
+                FloodDedup::Wgl | FloodDedup::Session => arrivals == 0,
+                FloodDedup::Paths(paths) => arrivals < paths,
+            };
+        // From here on the request is ours, it's moved along instead of copied
+        let Packet {
+            routing_header,
+            session_id,
+            pack_type: PacketType::FloodRequest(mut packet_id),
+        } = packet
+        else {
+            return;
+        };
+        if !forward {
+            // Already received this FloodReq, we need to build a FloodResponse
+            self.count(|c| c.suppressed_floods += 1);
+            packet_id.path_trace.push((self.id, NodeType::Drone));
+            self.send_packet(
+                Self::build_packet_flood_response(packet_id, session_id),
+                None,
+            );
+            return;
+        }
+        match packet_id.path_trace.last() {
+            None => self.log_info("Received Flood Request with empty path-trace! Throwing packet away. Drone doesn't know who to not send it back"),
+            Some(&(packetreceivedfrom, _)) => {
+                self.remember_flood(&packet_id, session_id); // **
+                if arrivals > 0 {
+                    self.count(|c| c.repeated_floods += 1);
+                }
                 packet_id
                     .path_trace
-                    .push((self.id.clone(), NodeType::Drone));
-                self.send_packet(
-                    Self::build_packet_flood_response(packet_id, packet.session_id),
-                    None,
-                );
-            } else {
-                match packet_id
-                    .path_trace
-                    .get(packet_id.path_trace.len() - 1usize)
-                {
-                    None => self
-                        .log("Received Flood Request with empty path-trace! Throwing packet away."),
-                    Some(packetreceivedfrom) => {
-                        floods_sent.push(packet_id.flood_id.clone()); // **
-                        let return_packet = Packet {
-                            session_id: packet.session_id,
-                            routing_header: packet.routing_header,
-                            pack_type: {
-                                let mut return_packet_typefield = packet_id.clone();
-                                return_packet_typefield
-                                    .path_trace
-                                    .push((self.id.clone(), NodeType::Drone)); // We adding our ID to the path trace
-                                PacketType::FloodRequest(return_packet_typefield)
-                            },
-                        };
-                        // We send the packet to all the neighbours beside the one we received the packet from
-                        for i in &mut self.packet_send.clone() {
-                            if *i.0 != packetreceivedfrom.0 {
-                                self.send_packet(return_packet.clone(), Some(i.1));
-                            }
-                        }
+                    .push((self.id, NodeType::Drone)); // We adding our ID to the path trace
+                let return_packet = Packet {
+                    session_id,
+                    routing_header,
+                    pack_type: PacketType::FloodRequest(packet_id),
+                };
+                // We send the packet to all the neighbours beside the one we received the packet from,
+                // the last one gets it without a copy
+                let neighbours: Vec<(NodeId, &Sender<Packet>)> = (self.packet_send.iter())
+                    .filter(|(id, _)| **id != packetreceivedfrom)
+                    .map(|(id, ch)| (*id, ch))
+                    .collect();
+                if let Some((last, others)) = neighbours.split_last() {
+                    for neighbour in others {
+                        self.send_packet(return_packet.clone(), Some(*neighbour));
                     }
+                    self.send_packet(return_packet, Some(*last));
                 }
             }
-        } else {
-            match packet_id.path_trace.get(packet_id.path_trace.len() - 1usize) {
-                    None => { self.log("Received Flood Request with empty path-trace! Throwing packet away. Drone doesn't know who to not send it back") },
-                    Some(packetreceivedfrom) => {
-                        self.cache.history_floodreq.insert(packet_id.initiator_id.clone(), vec![packet_id.flood_id.clone()]); // **
-                        let return_packet = Packet {
-                            session_id: packet.session_id,
-                            routing_header: packet.routing_header,
-                            pack_type: {
-                                let mut return_packet_typefield = packet_id.clone();
-                                return_packet_typefield.path_trace.push((self.id.clone(), NodeType::Drone)); // We adding our ID to the path trace
-                                PacketType::FloodRequest(return_packet_typefield)
-                            },
-                        };
-                        // We send the packet to all the neighbours beside the one we received the packet from
-                        for i in &mut self.packet_send.clone() {
-                            if *i.0 != packetreceivedfrom.0 {
-                                self.send_packet(return_packet.clone(), Some(i.1));
-                            }
-                        }
-                    }
+        }
+    }
+    // How many times we already forwarded this flood, as identified by config.flood_dedup.
+    fn flood_arrivals(&self, flood: &FloodRequest, session_id: u64) -> usize {
+        let by_session = self.cache.config.flood_dedup == FloodDedup::Session;
+        (self.cache.history_floodreq.get(&flood.initiator_id))
+            .and_then(|floods| {
+                floods.iter().find(|seen| {
+                    seen.flood_id == flood.flood_id
+                        && (!by_session || seen.session_id == session_id)
+                })
+            })
+            .map_or(0, |seen| seen.arrivals)
+    }
+    fn remember_flood(&mut self, flood: &FloodRequest, session_id: u64) {
+        let by_session = self.cache.config.flood_dedup == FloodDedup::Session;
+        let history_limit = self.cache.config.flood_history_limit;
+        let floods_sent = (self.cache.history_floodreq)
+            .entry(flood.initiator_id)
+            .or_default();
+        match floods_sent.iter_mut().find(|seen| {
+            seen.flood_id == flood.flood_id && (!by_session || seen.session_id == session_id)
+        }) {
+            Some(seen) => seen.arrivals += 1,
+            None => {
+                floods_sent.push(FloodRecord {
+                    flood_id: flood.flood_id,
+                    session_id,
+                    arrivals: 1,
+                });
+                if history_limit.is_some_and(|limit| floods_sent.len() > limit) {
+                    floods_sent.remove(0);
                 }
+            }
         }
     }
 
-    fn update_packet_to_forward(&self, mut packet: Packet) -> Packet {
-        // OK
-        // It is assumed that this function would be used only in the specified cases where it is used
-        // so it will indeed skip all the needed controls and will just update the hop
-        // index.
-        // [UPDATE]: Added a check if the next hop isn't available on the HashMap, in that case it will generate a nack
-        let nexthop = packet.routing_header.next_hop().unwrap();
-        if self.packet_send.contains_key(&nexthop) {
-            packet.routing_header.hop_index += 1;
-            packet
+    // False when the initiator is over its limit, a token is taken otherwise.
+    fn take_flood_token(&mut self, initiator: NodeId) -> bool {
+        let Some(limit) = self.cache.config.flood_rate_limit else {
+            return true;
+        };
+        let now = Instant::now();
+        let bucket = (self.cache.flood_buckets.entry(initiator)).or_insert(TokenBucket {
+            tokens: limit.burst as f64,
+            refilled: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.rate).min(limit.burst as f64);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
         } else {
-            // Genero un nack e lo rimando dove è tornato
-            let mut opt: Option<u64> = None;
-            match &packet.pack_type {
-                PacketType::MsgFragment(fragm) => opt = Some(fragm.fragment_index),
-                PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => {
-                    let _ = self.sendto_controller(packet.clone(), ControllerTypes::Shortcut);
-                }
-                _ => (),
-            }
-            Self::build_packet_nack(packet, ErrorInRouting(nexthop), opt)
+            false
         }
     }
+    // The flood isn't remembered, a copy coming from another neighbour gets the same treatment.
+    fn throttle_flood(&mut self, packet: Packet) {
+        let PacketType::FloodRequest(flood) = &packet.pack_type else {
+            return;
+        };
+        self.log_info(format!(
+            "Flood {} of {} over the rate limit",
+            flood.flood_id, flood.initiator_id
+        ));
+        self.count(|c| c.throttled_floods += 1);
+        let excess = (self.cache.config.flood_rate_limit).map(|limit| limit.excess);
+        let response = (excess == Some(ExcessFloodPolicy::Respond)).then(|| {
+            let mut flood = flood.clone();
+            flood.path_trace.push((self.id, NodeType::Drone));
+            Self::build_packet_flood_response(flood, packet.session_id)
+        });
+        let _ = self.sendto_controller(packet, ControllerTypes::Dropped);
+        if let Some(response) = response {
+            self.send_packet(response, None);
+        }
+    }
+
+    // Only for routes validate_route accepted: there is a next hop and it's a neighbour.
+    fn update_packet_to_forward(mut packet: Packet) -> Packet {
+        packet.routing_header.hop_index += 1;
+        packet
+    }
     fn build_packet_flood_response(flreq_header: FloodRequest, srcid: u64) -> Packet {
         let return_packet: Packet = Packet {
             routing_header: SourceRoutingHeader {
@@ -436,8 +859,8 @@ impl Drone {
                         .rev()
                         .map(|(id, _)| id)
                         .collect();
-                    if !rethop.last().unwrap().eq(&flreq_header.initiator_id) {
-                        rethop.push(flreq_header.initiator_id.clone());
+                    if rethop.last() != Some(&flreq_header.initiator_id) {
+                        rethop.push(flreq_header.initiator_id);
                     }
                     rethop
                 },
@@ -452,16 +875,18 @@ impl Drone {
         };
         return_packet
     }
+    // Only the routing header is copied, the packet can still be handed to the controller.
     fn build_packet_nack(
-        packet: Packet,
+        packet: &Packet,
         nack_id: NackType,
         optional_fragment_index: Option<u64>,
     ) -> Packet {
         Packet {
             session_id: packet.session_id,
             routing_header: {
-                let mut old_srh = packet.routing_header;
-                old_srh = old_srh.sub_route(0..=old_srh.hop_index).unwrap();
+                let srh = &packet.routing_header;
+                // Callers check the hop index, an out of bounds one keeps the whole route
+                let mut old_srh = (srh.sub_route(0..=srh.hop_index)).unwrap_or_else(|| srh.clone());
                 old_srh.reverse();
                 old_srh.hop_index = 1;
                 //println!("Building Nack [{:?}]...", nack_id);
@@ -473,51 +898,157 @@ impl Drone {
             }),
         }
     }
-    fn send_packet(&self, packet: Packet, channel: Option<&mut Sender<Packet>>) -> SendingCodes {
+    fn send_packet(
+        &self,
+        mut packet: Packet,
+        channel: Option<(NodeId, &Sender<Packet>)>,
+    ) -> SendingCodes {
         // OK
         self.log("Sending packet...");
+        let context = self.hook_context();
+        let next_hop = match &channel {
+            Some((id, _)) => Some(*id),
+            None => packet.routing_header.current_hop(),
+        };
+        match (self.cache.pipeline.borrow_mut()).before_forward(&context, &mut packet, next_hop) {
+            HookAction::Continue => {}
+            HookAction::Drop => {
+                self.log_info("Packet dropped by a hook");
+                return SendingCodes::HookDropped;
+            }
+            HookAction::Redirect(target) => return self.redirect(target, packet),
+        }
+        self.deliver(packet, channel)
+    }
+    // Nacks we built, sent along their header: before_nack instead of before_forward.
+    fn send_nack(&self, mut nack: Packet) -> SendingCodes {
+        self.log("Sending Nack...");
+        let context = self.hook_context();
+        match (self.cache.pipeline.borrow_mut()).before_nack(&context, &mut nack) {
+            HookAction::Continue => {}
+            HookAction::Drop => {
+                self.log_info("Packet dropped by a hook");
+                return SendingCodes::HookDropped;
+            }
+            HookAction::Redirect(target) => return self.redirect(target, nack),
+        }
+        self.count_nack(&nack);
+        self.deliver(nack, None)
+    }
+    // Sends on `channel`, or to the current hop of the header, once the hooks are done.
+    fn deliver(&self, packet: Packet, channel: Option<(NodeId, &Sender<Packet>)>) -> SendingCodes {
         match channel {
+            Some((_, ch)) => {
+                let session_id = packet.session_id;
+                match ch.send(packet) {
+                    Ok(_) => {
+                        // self.sendto_controller(packet, false); Ack to Sim. Controller
+                        self.log("Successfully sent packet...");
+                        self.count(|c| c.forwarded += 1);
+                        SendingCodes::SuccessfullySent(session_id)
+                    }
+                    Err(er) => {
+                        self.log_info(format!("{:?}", er.to_string()));
+                        self.count(|c| c.send_errors += 1);
+                        let reason = er.to_string();
+                        let _ = self.sendto_controller(er.into_inner(), ControllerTypes::Dropped); // We send the packet to Sim.Controller
+                        SendingCodes::ErrorSending(reason)
+                    }
+                }
+            }
+            None => match packet.routing_header.current_hop() {
+                Some(next_hop) => self.send_to(next_hop, packet),
+                None => {
+                    // A Nack of a packet that came from nowhere, e.g. a route made of us alone
+                    self.log_info(format!(
+                        "No next hop for [SESSION ID: {}]: {:?}",
+                        packet.session_id, packet.routing_header
+                    ));
+                    self.count(|c| c.send_errors += 1);
+                    let _ = self.sendto_controller(packet, ControllerTypes::Dropped);
+                    SendingCodes::NoNextHop("Route has no next hop".to_string())
+                }
+            },
+        }
+    }
+    // Nacks we built but can't route, they still go through before_nack.
+    fn nack_to_controller(&self, mut nack: Packet) {
+        let context = self.hook_context();
+        match (self.cache.pipeline.borrow_mut()).before_nack(&context, &mut nack) {
+            HookAction::Continue => {
+                self.count_nack(&nack);
+                let _ = self.sendto_controller(nack, ControllerTypes::Dropped);
+            }
+            HookAction::Drop => self.log_info("Packet dropped by a hook"),
+            HookAction::Redirect(target) => {
+                self.log_info(format!("{:?}", self.redirect(target, nack)));
+            }
+        }
+    }
+    fn count_nack(&self, packet: &Packet) {
+        if let PacketType::Nack(nack) = &packet.pack_type {
+            self.count(|c| match nack.nack_type {
+                ErrorInRouting(_) => c.nacked.error_in_routing += 1,
+                NackType::DestinationIsDrone => c.nacked.destination_is_drone += 1,
+                Dropped => c.nacked.dropped += 1,
+                UnexpectedRecipient(_) => c.nacked.unexpected_recipient += 1,
+            });
+        }
+    }
+    // HookAction::Redirect: `target` takes the place of the next hop, so it finds itself as
+    // the current hop when the packet gets there.
+    fn redirect(&self, target: NodeId, mut packet: Packet) -> SendingCodes {
+        if let PacketType::FloodRequest(_) = packet.pack_type {
+            return self.send_to(target, packet); // Their header isn't a route
+        }
+        let srh = &mut packet.routing_header;
+        if srh.current_hop() == Some(self.id) {
+            srh.hop_index += 1; // Still before routing
+        }
+        match srh.hop_index.cmp(&srh.hops.len()) {
+            std::cmp::Ordering::Less => srh.hops[srh.hop_index] = target,
+            std::cmp::Ordering::Equal => srh.hops.push(target),
+            std::cmp::Ordering::Greater => {} // Meaningless anyway, sent as it is
+        }
+        self.send_to(target, packet)
+    }
+    // Sends to a neighbour and reports it to the controller, no hook involved.
+    fn send_to(&self, target: NodeId, packet: Packet) -> SendingCodes {
+        match self.packet_send.get(&target) {
             Some(ch) => {
+                let session_id = packet.session_id;
+                // The controller gets the one copy we can't avoid
                 match ch.send(packet.clone()) {
                     Ok(_) => {
-                        // self.sendto_controller(packet, false); Ack to Sim. Controller
                         self.log("Successfully sent packet...");
-                        SendingCodes::SuccessfullySent(packet.session_id)
+                        self.count(|c| c.forwarded += 1);
+                        let _ = self.sendto_controller(packet, ControllerTypes::Sent); //Ack to Sim. Controller
+                        SendingCodes::SuccessfullySent(session_id)
                     }
                     Err(er) => {
-                        self.log(format!("{:?}", er.to_string()));
+                        self.log_info(er.to_string());
+                        self.count(|c| c.send_errors += 1);
                         let _ = self.sendto_controller(packet, ControllerTypes::Dropped); // We send the packet to Sim.Controller
                         SendingCodes::ErrorSending(er.to_string())
                     }
                 }
             }
             None => {
-                let channeltosend = self
-                    .packet_send
-                    .get(&packet.clone().routing_header.hops[packet.routing_header.hop_index]);
-                match channeltosend {
-                    Some(ch) => {
-                        match ch.send(packet.clone()) {
-                            Ok(_) => {
-                                self.log("Successfully sent packet...");
-                                let _ =
-                                    self.sendto_controller(packet.clone(), ControllerTypes::Sent); //Ack to Sim. Controller
-                                SendingCodes::SuccessfullySent(packet.session_id)
-                            }
-                            Err(er) => {
-                                self.log(format!("{}", er.to_string()));
-                                let _ = self.sendto_controller(packet, ControllerTypes::Dropped); // We send the packet to Sim.Controller
-                                SendingCodes::ErrorSending(er.to_string())
-                            }
-                        }
+                // Routes we build back toward the source can point at a node we aren't linked to
+                self.log_info(format!(
+                    "Neighbour {} not found for [SESSION ID: {}]",
+                    target, packet.session_id
+                ));
+                self.count(|c| c.send_errors += 1);
+                // Acks, Nacks and FloodResponses can't be lost, the controller delivers them
+                let kind = match packet.pack_type {
+                    PacketType::MsgFragment(_) | PacketType::FloodRequest(_) => {
+                        ControllerTypes::Dropped
                     }
-                    None => {
-                        // This is never going to be used
-                        //self.log("Neighbour not found...");
-                        //let _ = self.sendto_controller(packet, ControllerTypes::Dropped); // We send the packet to Sim.Controller
-                        SendingCodes::NoNextHop("Neighbour not found".to_string())
-                    }
-                }
+                    _ => ControllerTypes::Shortcut,
+                };
+                let _ = self.sendto_controller(packet, kind); // We send the packet to Sim.Controller
+                SendingCodes::NoNextHop("Neighbour not found".to_string())
             }
         }
     }
@@ -533,7 +1064,7 @@ impl Drone {
                         Ok(())
                     }
                     Err(er) => {
-                        self.log(format!("{}", er.to_string()));
+                        self.log_info(er.to_string());
                         Err(er.to_string())
                     }
                 }
@@ -545,12 +1076,13 @@ impl Drone {
                         Ok(())
                     }
                     Err(er) => {
-                        self.log(format!("{}", er.to_string()));
+                        self.log_info(er.to_string());
                         Err(er.to_string())
                     }
                 }
             }
             ControllerTypes::Shortcut => {
+                self.count(|c| c.shortcuts += 1);
                 match self
                     .controller_send
                     .send(DroneEvent::ControllerShortcut(packet))
@@ -560,7 +1092,7 @@ impl Drone {
                         Ok(())
                     }
                     Err(er) => {
-                        self.log(format!("{}", er.to_string()));
+                        self.log_info(er.to_string());
                         Err(er.to_string())
                     }
                 }
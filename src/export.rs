@@ -0,0 +1,143 @@
+use crate::network::Network;
+use serde::Serialize;
+use std::fmt::Write;
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+
+/// Picture of a running `Network`, rendered as DOT (Graphviz) or JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkSnapshot {
+    pub nodes: Vec<NodeState>,
+    pub links: Vec<LinkState>,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeState {
+    pub id: NodeId,
+    pub kind: &'static str, // "drone", "client" or "server"
+    pub pdr: Option<f32>,   // Drones only
+    pub crashed: bool,
+    pub forwarded: u64,
+    pub dropped: u64,
+    pub shortcuts: u64,
+    pub drop_rate: f64, // Observed: dropped / (forwarded + dropped)
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkState {
+    pub a: NodeId, // a < b
+    pub b: NodeId,
+    pub a_to_b: u64, // Packets sent, as reported by drones
+    pub b_to_a: u64,
+    pub up: bool, // false when one end crashed
+}
+
+impl NetworkSnapshot {
+    pub fn capture(network: &Network) -> Self {
+        let mut nodes = Vec::new();
+        let mut links = Vec::new();
+        for id in network.node_ids() {
+            let traffic = network.traffic_of(id);
+            let handled = traffic.forwarded + traffic.dropped;
+            nodes.push(NodeState {
+                id,
+                kind: match network.config.node_type(id) {
+                    Some(NodeType::Client) => "client",
+                    Some(NodeType::Server) => "server",
+                    _ => "drone",
+                },
+                pdr: network.pdr_of(id),
+                crashed: network.is_crashed(id),
+                forwarded: traffic.forwarded,
+                dropped: traffic.dropped,
+                shortcuts: traffic.shortcuts,
+                drop_rate: match handled {
+                    0 => 0.0,
+                    n => traffic.dropped as f64 / n as f64,
+                },
+            });
+            // Crashed drones lost their links, we still show the configured ones as down.
+            for n in network.config.neighbours_of(id) {
+                if id < n {
+                    links.push(LinkState {
+                        a: id,
+                        b: n,
+                        a_to_b: network.link_traffic(id, n),
+                        b_to_a: network.link_traffic(n, id),
+                        up: network.neighbours_of(id).contains(&n),
+                    });
+                }
+            }
+        }
+        // Links added at runtime aren't in the config.
+        for id in network.node_ids() {
+            for n in network.neighbours_of(id) {
+                if id < *n && !links.iter().any(|l| l.a == id && l.b == *n) {
+                    links.push(LinkState {
+                        a: id,
+                        b: *n,
+                        a_to_b: network.link_traffic(id, *n),
+                        b_to_a: network.link_traffic(*n, id),
+                        up: true,
+                    });
+                }
+            }
+        }
+        Self { nodes, links }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+    /// Crashed drones are red, drones with drops are shaded by their observed drop rate,
+    /// link width follows utilisation and links that went down are dashed.
+    pub fn to_dot(&self) -> String {
+        let busiest = (self.links.iter())
+            .map(|l| l.a_to_b + l.b_to_a)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut dot = String::from("graph network {\n    node [fontname=\"monospace\"];\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                "client" => "box",
+                "server" => "box3d",
+                _ => "ellipse",
+            };
+            let mut label = format!("{} {}", node.kind, node.id);
+            if let Some(pdr) = node.pdr {
+                let _ = write!(label, "\\npdr {:.2}", pdr);
+                let _ = write!(label, "\\nfwd {} drop {}", node.forwarded, node.dropped);
+            }
+            let style = if node.crashed {
+                label.push_str("\\nCRASHED");
+                "style=filled, fillcolor=\"#e06060\"".to_string()
+            } else if node.dropped > 0 {
+                // White to orange with the drop rate.
+                let shade = 255 - (node.drop_rate.clamp(0.0, 1.0) * 160.0) as u8;
+                format!(
+                    "style=filled, fillcolor=\"#ff{:02x}{:02x}\"",
+                    shade,
+                    shade / 2 + 64
+                )
+            } else {
+                "style=solid".to_string()
+            };
+            let _ = writeln!(
+                dot,
+                "    {} [shape={}, label=\"{}\", {}];",
+                node.id, shape, label, style
+            );
+        }
+        for link in &self.links {
+            let packets = link.a_to_b + link.b_to_a;
+            let width = 1.0 + 4.0 * packets as f64 / busiest as f64;
+            let style = if link.up { "solid" } else { "dashed" };
+            let _ = writeln!(
+                dot,
+                "    {} -- {} [label=\"{}/{}\", penwidth={:.1}, style={}];",
+                link.a, link.b, link.a_to_b, link.b_to_a, width, style
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::{BTreeMap, HashMap};
+use wg_2024::packet::{Fragment, FRAGMENT_DSIZE};
+
+/// Splits a message into `FRAGMENT_DSIZE` chunks. An empty message is still one fragment,
+/// otherwise the receiver would never know it existed.
+pub fn fragment_message(data: &[u8]) -> Vec<Fragment> {
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![&[]]
+    } else {
+        data.chunks(FRAGMENT_DSIZE).collect()
+    };
+    let total_n_fragments = chunks.len() as u64;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut data = [0u8; FRAGMENT_DSIZE];
+            data[..chunk.len()].copy_from_slice(chunk);
+            Fragment {
+                fragment_index: i as u64,
+                total_n_fragments,
+                length: chunk.len() as u8,
+                data,
+            }
+        })
+        .collect()
+}
+
+/// Collects fragments per session until every index arrived. Duplicates (retransmissions
+/// whose Ack got lost) are ignored.
+#[derive(Default)]
+pub struct Reassembler {
+    partial: HashMap<u64, BTreeMap<u64, Vec<u8>>>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the whole message once `fragment` completes `session_id`.
+    pub fn insert(&mut self, session_id: u64, fragment: &Fragment) -> Option<Vec<u8>> {
+        if fragment.fragment_index >= fragment.total_n_fragments {
+            return None; // Can never complete, don't keep it around
+        }
+        let length = (fragment.length as usize).min(FRAGMENT_DSIZE);
+        let received = self.partial.entry(session_id).or_default();
+        received
+            .entry(fragment.fragment_index)
+            .or_insert_with(|| fragment.data[..length].to_vec());
+        if received.len() as u64 == fragment.total_n_fragments {
+            self.partial
+                .remove(&session_id)
+                .map(|received| received.into_values().flatten().collect())
+        } else {
+            None
+        }
+    }
+    /// Sessions still waiting for fragments.
+    pub fn pending_sessions(&self) -> usize {
+        self.partial.len()
+    }
+}
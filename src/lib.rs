@@ -1,2 +1,23 @@
+#![allow(non_snake_case)] // The crate keeps the group name, LeDron_James
 mod drone;
-pub use drone::*;
\ No newline at end of file
+pub use drone::*;
+pub mod builder;
+pub mod client;
+pub mod codec;
+pub mod config;
+pub mod control;
+pub mod export;
+pub mod fragment;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod network;
+pub mod pipeline;
+pub mod route_quality;
+pub mod routing;
+pub mod scenario;
+pub mod scheduler;
+pub mod server;
+pub mod snapshot;
+pub mod status;
+pub mod topology;
+pub mod transport;
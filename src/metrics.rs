@@ -0,0 +1,188 @@
+use crate::scheduler::PacketClass;
+use crate::status::{DroneStatus, StatusBoard};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use wg_2024::network::NodeId;
+
+/// Serves the drones of `board` in the Prometheus text format on `127.0.0.1:port`,
+/// whatever the path requested. Port 0 picks a free one, the bound address is returned.
+pub fn serve(port: u16, board: StatusBoard) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A slow scraper shouldn't stall the next one
+            let board = board.clone();
+            thread::spawn(move || {
+                let _ = respond(stream, &board);
+            });
+        }
+    });
+    Ok(addr)
+}
+
+fn respond(stream: TcpStream, board: &StatusBoard) -> io::Result<()> {
+    // We don't care about the request, but it has to be read before answering.
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let body = render(board);
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+type Metric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&DroneStatus) -> f64,
+);
+
+// Name (without the ledron_drone_ prefix), type, help and value, one sample per drone.
+// ControlCommand::ResetStats takes the counters back to 0, which Prometheus sees as a reset.
+const METRICS: [Metric; 13] = [
+    ("pdr", "gauge", "Packet drop rate.", |s| s.pdr as f64),
+    ("crashed", "gauge", "1 once the drone crashed.", |s| {
+        s.crashed as u8 as f64
+    }),
+    (
+        "neighbours",
+        "gauge",
+        "Neighbours the drone can send to.",
+        |s| s.neighbours.len() as f64,
+    ),
+    (
+        "queue_depth",
+        "gauge",
+        "Packets waiting to be handled.",
+        |s| s.queue_depth as f64,
+    ),
+    (
+        "flood_history_entries",
+        "gauge",
+        "Flood ids remembered.",
+        |s| s.flood_history as f64,
+    ),
+    (
+        "forwarded_total",
+        "counter",
+        "Packets sent to a neighbour.",
+        |s| s.counters.forwarded as f64,
+    ),
+    (
+        "dropped_total",
+        "counter",
+        "Fragments dropped by the PDR.",
+        |s| s.counters.dropped as f64,
+    ),
+    (
+        "shortcuts_total",
+        "counter",
+        "Packets shortcut to the controller.",
+        |s| s.counters.shortcuts as f64,
+    ),
+    (
+        "send_errors_total",
+        "counter",
+        "Packets not sent to a neighbour.",
+        |s| s.counters.send_errors as f64,
+    ),
+    (
+        "invalid_routes_total",
+        "counter",
+        "Packets refused because of their route.",
+        |s| s.counters.invalid_routes as f64,
+    ),
+    (
+        "throttled_floods_total",
+        "counter",
+        "New floods over their initiator's rate limit.",
+        |s| s.counters.throttled_floods as f64,
+    ),
+    (
+        "suppressed_floods_total",
+        "counter",
+        "Duplicate floods answered instead of forwarded.",
+        |s| s.counters.suppressed_floods as f64,
+    ),
+    (
+        "repeated_floods_total",
+        "counter",
+        "Duplicate floods forwarded to enumerate more paths.",
+        |s| s.counters.repeated_floods as f64,
+    ),
+];
+
+/// Text exposition of every drone that published a status, sorted by id.
+pub fn render(board: &StatusBoard) -> String {
+    let mut drones: Vec<(NodeId, DroneStatus)> = board.all().into_iter().collect();
+    drones.sort_by_key(|(id, _)| *id);
+    let mut out = String::new();
+    for (name, kind, help, value) in METRICS {
+        let _ = writeln!(out, "# HELP ledron_drone_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE ledron_drone_{} {}", name, kind);
+        for (id, status) in &drones {
+            let _ = writeln!(
+                out,
+                "ledron_drone_{}{{drone=\"{}\"}} {}",
+                name,
+                id,
+                value(status)
+            );
+        }
+    }
+    let _ = writeln!(
+        out,
+        "# HELP ledron_drone_nacked_total Nacks generated, by type."
+    );
+    let _ = writeln!(out, "# TYPE ledron_drone_nacked_total counter");
+    for (id, status) in &drones {
+        let nacked = status.counters.nacked;
+        for (kind, value) in [
+            ("ErrorInRouting", nacked.error_in_routing),
+            ("DestinationIsDrone", nacked.destination_is_drone),
+            ("Dropped", nacked.dropped),
+            ("UnexpectedRecipient", nacked.unexpected_recipient),
+        ] {
+            let _ = writeln!(
+                out,
+                "ledron_drone_nacked_total{{drone=\"{}\",type=\"{}\"}} {}",
+                id, kind, value
+            );
+        }
+    }
+    // Summary of the time spent in the internal queue, see Scheduling
+    let _ = writeln!(
+        out,
+        "# HELP ledron_drone_queue_delay_seconds Time packets waited in the drone queue, by class."
+    );
+    let _ = writeln!(out, "# TYPE ledron_drone_queue_delay_seconds summary");
+    for (id, status) in &drones {
+        for class in PacketClass::ALL {
+            let delay = status.queue_delays.get(class);
+            let labels = format!("drone=\"{}\",class=\"{:?}\"", id, class);
+            let _ = writeln!(
+                out,
+                "ledron_drone_queue_delay_seconds_sum{{{}}} {}",
+                labels,
+                delay.total_us as f64 / 1e6
+            );
+            let _ = writeln!(
+                out,
+                "ledron_drone_queue_delay_seconds_count{{{}}} {}",
+                labels, delay.served
+            );
+        }
+    }
+    out
+}
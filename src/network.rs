@@ -0,0 +1,566 @@
+use crate::builder::{DroneBuilder, DroneConfig};
+use crate::config::NetworkConfig;
+use crate::control::{self, ControlCommand};
+use crate::routing::reversed_route;
+use crate::snapshot::NetworkCheckpoint;
+use crate::status::{DroneState, DroneStatus, StatusBoard};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use wg_2024::controller::*;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+/// Counters collected by the network while polling, printed by the CLI at exit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkStats {
+    pub delivered: u64, // Non-Nack packets that reached a client/server
+    pub nacked: u64,    // Nacks that reached a client/server
+    pub dropped: u64,   // PacketDropped events
+    pub sent: u64,      // PacketSent events
+    pub shortcuts: u64, // ControllerShortcut events
+}
+
+/// Per-drone counters, from the events the drone reported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DroneTraffic {
+    pub forwarded: u64, // PacketSent
+    pub dropped: u64,   // PacketDropped
+    pub shortcuts: u64, // ControllerShortcut
+}
+
+/// Anything that happened since the last `poll`.
+#[derive(Debug, Clone)]
+pub enum NetworkEvent {
+    Drone(NodeId, DroneEvent), // Event reported by a drone to the controller
+    Received(NodeId, Packet),  // Packet that reached a client/server
+}
+
+/// Inbox of a node and a sender to each of its neighbours, see `Network::attach`.
+pub type NodeChannels = (Receiver<Packet>, HashMap<NodeId, Sender<Packet>>);
+
+struct DroneHandle {
+    command_send: Sender<DroneCommand>,
+    control_send: Sender<ControlCommand>,
+    event_recv: Receiver<DroneEvent>,
+    thread: Option<JoinHandle<()>>,
+    pdr: f32,
+    crashed: bool,
+}
+
+/// In-process network built from a `NetworkConfig`: every drone runs `Drone::run` on its own
+/// thread, clients and servers are plain inboxes owned by the network, which also plays
+/// the Simulation Controller.
+pub struct Network {
+    pub config: NetworkConfig,
+    drones: HashMap<NodeId, DroneHandle>,
+    inboxes: HashMap<NodeId, Sender<Packet>>, // Packet sender of every node still alive
+    edges: HashMap<NodeId, Receiver<Packet>>, // Clients and servers
+    links: HashMap<NodeId, Vec<NodeId>>,      // Current neighbours of every node
+    drone_config: DroneConfig,                // Applied to every drone we start
+    auto_ack: bool,
+    status: StatusBoard,
+    stats: NetworkStats,
+    traffic: HashMap<NodeId, DroneTraffic>,
+    link_traffic: HashMap<(NodeId, NodeId), u64>, // (from, to) -> packets sent
+}
+
+impl Network {
+    /// Panics if a drone has a pdr outside 0..=1, see `spawn_with`.
+    pub fn spawn(config: NetworkConfig, logging: bool) -> Self {
+        let drone_config = DroneConfig {
+            logging,
+            ..DroneConfig::default()
+        };
+        Self::spawn_with(config, drone_config).unwrap_or_else(|er| panic!("{}", er))
+    }
+    /// Like `spawn`, with the same `DroneConfig` for every drone (seeds are mixed with the ids).
+    pub fn spawn_with(config: NetworkConfig, drone_config: DroneConfig) -> Result<Self, String> {
+        drone_config.validate()?;
+        let mut network = Network {
+            drones: HashMap::new(),
+            inboxes: HashMap::new(),
+            edges: HashMap::new(),
+            links: HashMap::new(),
+            drone_config,
+            auto_ack: true,
+            status: StatusBoard::new(),
+            stats: NetworkStats::default(),
+            traffic: HashMap::new(),
+            link_traffic: HashMap::new(),
+            config,
+        };
+        let mut receivers = HashMap::new();
+        for id in network.node_ids() {
+            let (tx, rx) = match network.config.pdr_of(id) {
+                Some(_) => network.drone_config.packet_channel(),
+                None => unbounded::<Packet>(),
+            };
+            network.inboxes.insert(id, tx);
+            receivers.insert(id, rx);
+            network.links.insert(id, network.config.neighbours_of(id));
+        }
+        for (id, rx) in receivers {
+            match network.config.pdr_of(id) {
+                Some(pdr) => {
+                    let builder = DroneBuilder::new(id)
+                        .config(network.drone_config.clone())
+                        .pdr(pdr);
+                    network.start_drone(builder, rx)?;
+                }
+                None => {
+                    network.edges.insert(id, rx);
+                }
+            }
+        }
+        Ok(network)
+    }
+    /// Network in the state of `checkpoint`, drones on fresh threads and channels.
+    pub fn restore(checkpoint: NetworkCheckpoint) -> Result<Self, String> {
+        checkpoint.drone_config.validate()?;
+        let mut network = Network {
+            drones: HashMap::new(),
+            inboxes: HashMap::new(),
+            edges: HashMap::new(),
+            links: checkpoint.links.into_iter().collect(),
+            drone_config: checkpoint.drone_config,
+            auto_ack: true,
+            status: StatusBoard::new(),
+            stats: checkpoint.stats,
+            traffic: HashMap::new(),
+            link_traffic: HashMap::new(),
+            config: checkpoint.config,
+        };
+        // Crashed drones have no inbox, like after `crash`.
+        let crashed: HashMap<NodeId, f32> = checkpoint.crashed.into_iter().collect();
+        let mut receivers = HashMap::new();
+        for id in network.node_ids() {
+            if crashed.contains_key(&id) {
+                continue;
+            }
+            let (tx, rx) = match network.config.pdr_of(id) {
+                Some(_) => network.drone_config.packet_channel(),
+                None => unbounded::<Packet>(),
+            };
+            network.inboxes.insert(id, tx);
+            receivers.insert(id, rx);
+        }
+        for snapshot in checkpoint.drones {
+            let id = snapshot.state.id;
+            let rx =
+                (receivers.remove(&id)).ok_or(format!("Drone {} isn't in the topology", id))?;
+            network.start_drone(DroneBuilder::from_snapshot(snapshot), rx)?;
+        }
+        for (id, rx) in receivers {
+            if network.config.pdr_of(id).is_some() {
+                return Err(format!("Drone {} is missing from the checkpoint", id));
+            }
+            network.edges.insert(id, rx);
+        }
+        for (id, pdr) in crashed {
+            // Nothing left to talk to, `recover` replaces the handle.
+            let (command_send, _) = unbounded();
+            let (control_send, _) = unbounded();
+            let (_, event_recv) = unbounded();
+            network.drones.insert(
+                id,
+                DroneHandle {
+                    command_send,
+                    control_send,
+                    event_recv,
+                    thread: None,
+                    pdr,
+                    crashed: true,
+                },
+            );
+        }
+        Ok(network)
+    }
+    /// Snapshot of every running drone, taken one after the other while they keep going:
+    /// settle the network first for a consistent checkpoint.
+    pub fn checkpoint(&self) -> Result<NetworkCheckpoint, String> {
+        let mut ids: Vec<NodeId> = self.drones.keys().copied().collect();
+        ids.sort();
+        let mut drones = Vec::new();
+        let mut crashed = Vec::new();
+        for id in ids {
+            let drone = &self.drones[&id];
+            if drone.crashed {
+                crashed.push((id, drone.pdr));
+            } else {
+                let snapshot = control::snapshot(&drone.control_send, Duration::from_secs(1))
+                    .map_err(|er| format!("Drone {}: {}", id, er))?;
+                drones.push(snapshot);
+            }
+        }
+        Ok(NetworkCheckpoint {
+            config: self.config.clone(),
+            drone_config: self.drone_config.clone(),
+            links: self.links.iter().map(|(id, n)| (*id, n.clone())).collect(),
+            drones,
+            crashed,
+            stats: self.stats.clone(),
+        })
+    }
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = (self.config.drone.iter().map(|d| d.id))
+            .chain(self.config.client.iter().map(|c| c.id))
+            .chain(self.config.server.iter().map(|s| s.id))
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+    pub fn neighbours_of(&self, id: NodeId) -> &[NodeId] {
+        self.links.get(&id).map(|n| n.as_slice()).unwrap_or(&[])
+    }
+    pub fn is_drone(&self, id: NodeId) -> bool {
+        self.drones.contains_key(&id)
+    }
+    pub fn is_crashed(&self, id: NodeId) -> bool {
+        self.drones.get(&id).is_some_and(|d| d.crashed)
+    }
+    pub fn pdr_of(&self, id: NodeId) -> Option<f32> {
+        self.drones.get(&id).map(|d| d.pdr)
+    }
+    /// Hands the packet channels of client/server `id` to the caller, e.g. to run a `Client`
+    /// or a `Server` on them: its inbox and a sender to every neighbour. The network stops
+    /// polling (and acking for) it but still delivers the shortcuts addressed to it.
+    pub fn attach(&mut self, id: NodeId) -> Result<NodeChannels, String> {
+        let packet_recv = (self.edges.remove(&id)).ok_or(format!(
+            "{} isn't a client/server or is attached already",
+            id
+        ))?;
+        let packet_send = (self.neighbours_of(id).iter())
+            .filter_map(|n| self.inboxes.get(n).map(|tx| (*n, tx.clone())))
+            .collect();
+        Ok((packet_recv, packet_send))
+    }
+    pub fn set_auto_ack(&mut self, auto_ack: bool) {
+        self.auto_ack = auto_ack;
+    }
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+    /// Last status published by the drone itself.
+    pub fn status_of(&self, id: NodeId) -> Option<DroneStatus> {
+        self.status.get(id)
+    }
+    /// Board every drone of the network publishes to, e.g. for the metrics endpoint.
+    pub fn status_board(&self) -> StatusBoard {
+        self.status.clone()
+    }
+    pub fn traffic_of(&self, id: NodeId) -> DroneTraffic {
+        self.traffic.get(&id).copied().unwrap_or_default()
+    }
+    /// Packets `from` reported as sent to `to`.
+    pub fn link_traffic(&self, from: NodeId, to: NodeId) -> u64 {
+        self.link_traffic.get(&(from, to)).copied().unwrap_or(0)
+    }
+
+    // Wires the drone of `builder` to its neighbours and the network, then runs it.
+    fn start_drone(
+        &mut self,
+        builder: DroneBuilder,
+        packet_recv: Receiver<Packet>,
+    ) -> Result<(), String> {
+        let (command_send, controller_recv) = unbounded();
+        let (controller_send, event_recv) = unbounded();
+        let mut drone = builder
+            .controller(controller_send, controller_recv)
+            .packet_recv(packet_recv)
+            .build()?;
+        for n in self.neighbours_of(drone.id) {
+            if let Some(tx) = self.inboxes.get(n) {
+                drone.packet_send.insert(*n, tx.clone());
+            }
+        }
+        let (id, pdr) = (drone.id, drone.pdr);
+        drone.status_board(self.status.clone());
+        let control_send = drone.control_channel();
+        let thread = thread::Builder::new()
+            .name(format!("ledron-{}", id))
+            .spawn(move || drone.run())
+            .expect("Failed to spawn drone thread");
+        self.drones.insert(
+            id,
+            DroneHandle {
+                command_send,
+                control_send,
+                event_recv,
+                thread: Some(thread),
+                pdr,
+                crashed: false,
+            },
+        );
+        Ok(())
+    }
+
+    /// Hands `packet` to the node at `hops[hop_index]`, like `from` would do.
+    /// Flood requests are sent to every neighbour of `from` instead.
+    pub fn inject(&self, from: NodeId, packet: Packet) -> Result<(), String> {
+        let targets: Vec<NodeId> = match &packet.pack_type {
+            PacketType::FloodRequest(_) => self.neighbours_of(from).to_vec(),
+            _ => match packet.routing_header.current_hop() {
+                Some(hop) if self.neighbours_of(from).contains(&hop) => vec![hop],
+                Some(hop) => return Err(format!("{} is not a neighbour of {}", hop, from)),
+                None => return Err("Routing header has no current hop".to_string()),
+            },
+        };
+        for target in targets {
+            match self.inboxes.get(&target) {
+                Some(tx) => tx.send(packet.clone()).map_err(|er| er.to_string())?,
+                None => return Err(format!("Node {} is gone", target)),
+            }
+        }
+        Ok(())
+    }
+    pub fn flood(&self, from: NodeId, flood_id: u64) -> Result<(), String> {
+        let node_type = self
+            .config
+            .node_type(from)
+            .ok_or(format!("Unknown node {}", from))?;
+        self.inject(
+            from,
+            Packet {
+                session_id: flood_id,
+                routing_header: SourceRoutingHeader {
+                    hop_index: 0,
+                    hops: vec![],
+                },
+                pack_type: PacketType::FloodRequest(FloodRequest {
+                    flood_id,
+                    initiator_id: from,
+                    path_trace: vec![(from, node_type)],
+                }),
+            },
+        )
+    }
+    /// Sends `count` fragments of `session_id` along `path`, which starts with the sender.
+    pub fn send_fragments(
+        &self,
+        path: Vec<NodeId>,
+        count: u64,
+        session_id: u64,
+    ) -> Result<(), String> {
+        let Some(&from) = path.first() else {
+            return Err("Empty path".to_string());
+        };
+        for fragment_index in 0..count {
+            self.inject(
+                from,
+                Packet {
+                    session_id,
+                    routing_header: SourceRoutingHeader {
+                        hop_index: 1,
+                        hops: path.clone(),
+                    },
+                    pack_type: PacketType::MsgFragment(Fragment {
+                        fragment_index,
+                        total_n_fragments: count,
+                        length: FRAGMENT_DSIZE as u8,
+                        data: [fragment_index as u8; FRAGMENT_DSIZE],
+                    }),
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sends a crate-specific command to a drone, fails once a crashed drone stopped.
+    pub fn control(&self, id: NodeId, command: ControlCommand) -> Result<(), String> {
+        let drone = self
+            .drones
+            .get(&id)
+            .ok_or(format!("Unknown drone {}", id))?;
+        (drone.control_send.send(command)).map_err(|er| er.to_string())
+    }
+    /// State straight from the drone, unlike `status_of` which may be a few packets behind.
+    pub fn query_state(&self, id: NodeId) -> Result<DroneStatus, String> {
+        let drone = self
+            .drones
+            .get(&id)
+            .ok_or(format!("Unknown drone {}", id))?;
+        control::query_state(&drone.control_send, Duration::from_secs(1))
+    }
+    pub fn dump_state(&self, id: NodeId) -> Result<DroneState, String> {
+        let drone = self
+            .drones
+            .get(&id)
+            .ok_or(format!("Unknown drone {}", id))?;
+        control::dump_state(&drone.control_send, Duration::from_secs(1))
+    }
+    /// `dump_state` of every drone, in id order.
+    pub fn dump_all(&self) -> Vec<(NodeId, Result<DroneState, String>)> {
+        let mut ids: Vec<NodeId> = self.drones.keys().copied().collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| (id, self.dump_state(id)))
+            .collect()
+    }
+    pub fn set_pdr(&mut self, id: NodeId, pdr: f32) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&pdr) {
+            return Err(format!("Invalid pdr {}", pdr));
+        }
+        let drone = self
+            .drones
+            .get_mut(&id)
+            .ok_or(format!("Unknown drone {}", id))?;
+        drone
+            .command_send
+            .send(DroneCommand::SetPacketDropRate(pdr))
+            .map_err(|er| er.to_string())?;
+        drone.pdr = pdr;
+        Ok(())
+    }
+    /// WGL crash procedure: Crash to the drone, RemoveSender to its neighbours. The drone
+    /// keeps draining its queue and exits once nobody holds its packet sender anymore.
+    pub fn crash(&mut self, id: NodeId) -> Result<(), String> {
+        let drone = self
+            .drones
+            .get_mut(&id)
+            .ok_or(format!("Unknown drone {}", id))?;
+        if drone.crashed {
+            return Err(format!("Drone {} already crashed", id));
+        }
+        drone.crashed = true;
+        let _ = drone.command_send.send(DroneCommand::Crash);
+        for neighbour in self.links.remove(&id).unwrap_or_default() {
+            if let Some(d) = self.drones.get(&neighbour) {
+                let _ = d.command_send.send(DroneCommand::RemoveSender(id));
+            }
+            if let Some(links) = self.links.get_mut(&neighbour) {
+                links.retain(|n| *n != id);
+            }
+        }
+        self.links.insert(id, vec![]);
+        self.inboxes.remove(&id);
+        Ok(())
+    }
+    /// Brings a crashed drone back as a fresh instance wired to its configured neighbours.
+    pub fn recover(&mut self, id: NodeId) -> Result<(), String> {
+        let pdr = match self.drones.get(&id) {
+            Some(d) if d.crashed => d.pdr,
+            Some(_) => return Err(format!("Drone {} is not crashed", id)),
+            None => return Err(format!("Unknown drone {}", id)),
+        };
+        if let Some(mut old) = self.drones.remove(&id) {
+            // Closing the command channel makes the old instance return if it's still draining.
+            drop(old.command_send);
+            if let Some(thread) = old.thread.take() {
+                let _ = thread.join();
+            }
+        }
+        let (tx, rx) = self.drone_config.packet_channel();
+        self.inboxes.insert(id, tx.clone());
+        let neighbours: Vec<NodeId> = (self.config.neighbours_of(id).into_iter())
+            .filter(|n| self.inboxes.contains_key(n))
+            .collect();
+        for n in &neighbours {
+            if let Some(d) = self.drones.get(n) {
+                let _ = d.command_send.send(DroneCommand::AddSender(id, tx.clone()));
+            }
+            if let Some(links) = self.links.get_mut(n) {
+                links.push(id);
+            }
+        }
+        self.links.insert(id, neighbours);
+        let builder = DroneBuilder::new(id)
+            .config(self.drone_config.clone())
+            .pdr(pdr);
+        self.start_drone(builder, rx)
+    }
+
+    /// Collects drone events and packets that reached clients/servers, updating the stats.
+    /// Shortcuts are delivered straight to the destination, as the controller is supposed to.
+    /// Fragments reaching their destination are acknowledged unless `set_auto_ack(false)`.
+    pub fn poll(&mut self) -> Vec<NetworkEvent> {
+        let mut events = Vec::new();
+        for (id, drone) in &self.drones {
+            for event in drone.event_recv.try_iter() {
+                let traffic = self.traffic.entry(*id).or_default();
+                match &event {
+                    DroneEvent::PacketSent(packet) => {
+                        self.stats.sent += 1;
+                        traffic.forwarded += 1;
+                        // hop_index already points to the receiver when the event is sent.
+                        if let Some(to) = packet.routing_header.current_hop() {
+                            *self.link_traffic.entry((*id, to)).or_default() += 1;
+                        }
+                    }
+                    DroneEvent::PacketDropped(_) => {
+                        self.stats.dropped += 1;
+                        traffic.dropped += 1;
+                    }
+                    DroneEvent::ControllerShortcut(packet) => {
+                        self.stats.shortcuts += 1;
+                        traffic.shortcuts += 1;
+                        if let Some(tx) = (packet.routing_header.hops.last())
+                            .filter(|dest| !self.drones.contains_key(dest))
+                            .and_then(|dest| self.inboxes.get(dest))
+                        {
+                            let _ = tx.send(packet.clone());
+                        }
+                    }
+                }
+                events.push(NetworkEvent::Drone(*id, event));
+            }
+        }
+        let mut acks = Vec::new();
+        for (id, inbox) in &self.edges {
+            for packet in inbox.try_iter() {
+                match &packet.pack_type {
+                    PacketType::Nack(_) => self.stats.nacked += 1,
+                    PacketType::MsgFragment(fragment) => {
+                        self.stats.delivered += 1;
+                        if self.auto_ack && packet.routing_header.is_last_hop() {
+                            acks.push((*id, Self::build_ack(&packet, fragment.fragment_index)));
+                        }
+                    }
+                    _ => self.stats.delivered += 1,
+                }
+                events.push(NetworkEvent::Received(*id, packet));
+            }
+        }
+        for (id, ack) in acks {
+            let _ = self.inject(id, ack);
+        }
+        events
+    }
+    // Ack routed back along the reversed path the fragment took.
+    fn build_ack(fragment: &Packet, fragment_index: u64) -> Packet {
+        Packet {
+            session_id: fragment.session_id,
+            routing_header: reversed_route(&fragment.routing_header),
+            pack_type: PacketType::Ack(Ack { fragment_index }),
+        }
+    }
+    /// Polls until nothing happens for `idle`, returns everything collected.
+    pub fn settle(&mut self, idle: Duration) -> Vec<NetworkEvent> {
+        let mut events = Vec::new();
+        loop {
+            thread::sleep(idle);
+            let new = self.poll();
+            if new.is_empty() {
+                return events;
+            }
+            events.extend(new);
+        }
+    }
+    /// Closes every command channel and waits for the drone threads.
+    pub fn shutdown(mut self) -> NetworkStats {
+        self.poll();
+        self.inboxes.clear();
+        let mut threads = Vec::new();
+        for (_, mut drone) in self.drones.drain() {
+            threads.extend(drone.thread.take());
+        }
+        for thread in threads {
+            let _ = thread.join();
+        }
+        self.stats
+    }
+}
@@ -0,0 +1,99 @@
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+/// What the drone looks like to a hook when it's called.
+#[derive(Debug, Clone, Copy)]
+pub struct HookContext {
+    pub drone: NodeId,
+    pub pdr: f32,
+    pub crashed: bool,
+}
+
+/// Outcome of a hook, the first one not returning `Continue` decides for the packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookAction {
+    Continue,
+    Drop,             // The packet silently disappears, nobody is told
+    Redirect(NodeId), // Sent to this neighbour in place of the next hop, reported as sent
+}
+
+/// Inspector, filter or transformer plugged into the drone with `Drone::add_hook` or
+/// `DroneBuilder::hook`. Every stage can modify the packet, by default it lets it through.
+///
+/// ```ignore
+/// struct NoFloodsFrom(NodeId); // An ACL
+/// impl PacketHook for NoFloodsFrom {
+///     fn before_routing(&mut self, _: &HookContext, packet: &mut Packet) -> HookAction {
+///         match &packet.pack_type {
+///             PacketType::FloodRequest(f) if f.initiator_id == self.0 => HookAction::Drop,
+///             _ => HookAction::Continue,
+///         }
+///     }
+/// }
+/// ```
+pub trait PacketHook: Send {
+    /// Every packet the drone receives, before its routing header is checked.
+    fn before_routing(&mut self, _ctx: &HookContext, _packet: &mut Packet) -> HookAction {
+        HookAction::Continue
+    }
+    /// Every packet about to be sent to `next_hop` (None when the header has none),
+    /// forwarded floods included. Nacks built by the drone go through `before_nack` instead.
+    fn before_forward(
+        &mut self,
+        _ctx: &HookContext,
+        _packet: &mut Packet,
+        _next_hop: Option<NodeId>,
+    ) -> HookAction {
+        HookAction::Continue
+    }
+    /// Nacks built by the drone, right before they're sent back, or handed to the controller
+    /// when their route is unknown. The Nack is already built: its header leads back to the
+    /// sender of the packet being nacked.
+    fn before_nack(&mut self, _ctx: &HookContext, _nack: &mut Packet) -> HookAction {
+        HookAction::Continue
+    }
+}
+
+/// Hooks of a drone, run in the order they were added.
+#[derive(Default)]
+pub struct Pipeline {
+    hooks: Vec<Box<dyn PacketHook>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, hook: Box<dyn PacketHook>) {
+        self.hooks.push(hook);
+    }
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+    pub fn before_routing(&mut self, ctx: &HookContext, packet: &mut Packet) -> HookAction {
+        self.run(|hook| hook.before_routing(ctx, packet))
+    }
+    pub fn before_forward(
+        &mut self,
+        ctx: &HookContext,
+        packet: &mut Packet,
+        next_hop: Option<NodeId>,
+    ) -> HookAction {
+        self.run(|hook| hook.before_forward(ctx, packet, next_hop))
+    }
+    pub fn before_nack(&mut self, ctx: &HookContext, nack: &mut Packet) -> HookAction {
+        self.run(|hook| hook.before_nack(ctx, nack))
+    }
+    fn run(&mut self, mut stage: impl FnMut(&mut dyn PacketHook) -> HookAction) -> HookAction {
+        for hook in &mut self.hooks {
+            match stage(hook.as_mut()) {
+                HookAction::Continue => {}
+                action => return action,
+            }
+        }
+        HookAction::Continue
+    }
+}
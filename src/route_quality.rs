@@ -0,0 +1,164 @@
+use crate::topology::Topology;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+// Estimates never reach 1, otherwise a single unlucky drone would be unroutable forever.
+const MAX_PDR: f64 = 0.99;
+// Tie breaker, between routes equally reliable the shorter wins.
+const HOP_COST: f64 = 1e-6;
+
+/// Per-drone packet drop rate estimated from the Nacks and Acks a node receives, used to
+/// pick the route with the best end-to-end delivery probability instead of the shortest.
+///
+/// Every observation is a forwarding attempt of a fragment by a drone: a `Dropped` Nack is a
+/// failure of the drone that generated it (first hop of the Nack header), the drones it went
+/// through before are successes, and an Ack is a success for every drone of the path.
+/// An `ErrorInRouting(n)` Nack counts as a failure of `n`, which couldn't be reached.
+/// Old observations decay with `half_life`, so a drone whose PDR changes is re-evaluated.
+pub struct RouteQuality {
+    estimates: HashMap<NodeId, Estimate>,
+    prior_pdr: f64,      // Estimate of a drone we know nothing about
+    prior_weight: f64,   // How many attempts the prior is worth
+    half_life: Duration, // Observations lose half their weight after this
+}
+
+struct Estimate {
+    attempts: f64,
+    drops: f64,
+    updated: Instant,
+}
+
+impl Default for RouteQuality {
+    fn default() -> Self {
+        Self::new(0.1, Duration::from_secs(30))
+    }
+}
+
+impl RouteQuality {
+    pub fn new(prior_pdr: f64, half_life: Duration) -> Self {
+        Self {
+            estimates: HashMap::new(),
+            prior_pdr: prior_pdr.clamp(0.0, MAX_PDR),
+            prior_weight: 4.0,
+            half_life,
+        }
+    }
+    /// Updates the estimates from a Nack or Ack received by this node, ignores anything else.
+    pub fn record(&mut self, packet: &Packet) {
+        let hops = &packet.routing_header.hops;
+        match &packet.pack_type {
+            PacketType::Nack(nack) => {
+                let Some(reporter) = hops.first().copied() else {
+                    return;
+                };
+                // hops[1..len-1] forwarded the fragment up to the reporter.
+                for drone in hops.iter().skip(1).take(hops.len().saturating_sub(2)) {
+                    self.observe(*drone, false);
+                }
+                match nack.nack_type {
+                    NackType::Dropped => self.observe(reporter, true),
+                    NackType::ErrorInRouting(unreachable) => self.observe(unreachable, true),
+                    NackType::DestinationIsDrone | NackType::UnexpectedRecipient(_) => {}
+                }
+            }
+            PacketType::Ack(_) => {
+                // Acks aren't dropped, so the fragment made it through every drone in between.
+                for drone in hops.iter().skip(1).take(hops.len().saturating_sub(2)) {
+                    self.observe(*drone, false);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn observe(&mut self, drone: NodeId, dropped: bool) {
+        let now = Instant::now();
+        let decay = self.decay_factor(now);
+        let estimate = self.estimates.entry(drone).or_insert(Estimate {
+            attempts: 0.0,
+            drops: 0.0,
+            updated: now,
+        });
+        let factor = decay(estimate.updated);
+        estimate.attempts = estimate.attempts * factor + 1.0;
+        estimate.drops = estimate.drops * factor + if dropped { 1.0 } else { 0.0 };
+        estimate.updated = now;
+    }
+    fn decay_factor(&self, now: Instant) -> impl Fn(Instant) -> f64 {
+        let half_life = self.half_life.as_secs_f64();
+        move |since: Instant| {
+            if half_life <= 0.0 {
+                return 0.0;
+            }
+            0.5f64.powf(now.duration_since(since).as_secs_f64() / half_life)
+        }
+    }
+
+    /// Current estimate for `drone`, the prior if we never heard of it.
+    pub fn pdr(&self, drone: NodeId) -> f64 {
+        let Some(estimate) = self.estimates.get(&drone) else {
+            return self.prior_pdr;
+        };
+        let factor = self.decay_factor(Instant::now())(estimate.updated);
+        let attempts = estimate.attempts * factor + self.prior_weight;
+        let drops = estimate.drops * factor + self.prior_pdr * self.prior_weight;
+        (drops / attempts).min(MAX_PDR)
+    }
+    /// Probability that a fragment sent along `path` (sender and destination included)
+    /// is not dropped by any drone in between.
+    pub fn delivery_probability(&self, path: &[NodeId]) -> f64 {
+        (path.iter().skip(1).take(path.len().saturating_sub(2)))
+            .map(|drone| 1.0 - self.pdr(*drone))
+            .product()
+    }
+
+    /// Path maximising the delivery probability, i.e. the shortest path when every drone
+    /// weighs -ln(1 - pdr). Like `Topology::shortest_path`, only drones are crossed.
+    pub fn best_path(&self, topology: &Topology, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+        // At most 256 nodes, a plain O(V^2) Dijkstra is plenty.
+        let mut cost: HashMap<NodeId, f64> = HashMap::from([(from, 0.0)]);
+        let mut previous: HashMap<NodeId, NodeId> = HashMap::new();
+        let mut done: Vec<NodeId> = Vec::new();
+        loop {
+            let (node, node_cost) = (cost.iter())
+                .filter(|(id, _)| !done.contains(id))
+                .min_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(id, c)| (*id, *c))?;
+            if node == to {
+                let mut path = vec![to];
+                while let Some(prev) = previous.get(path.last()?) {
+                    path.push(*prev);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            done.push(node);
+            if node != from && !topology.is_drone(node) {
+                continue;
+            }
+            // Whatever we cross costs the drop probability of the node we leave from.
+            let leave = if node == from {
+                0.0
+            } else {
+                HOP_COST - (1.0 - self.pdr(node)).ln()
+            };
+            for next in topology.neighbours(node) {
+                let next_cost = node_cost + leave;
+                if !done.contains(&next) && cost.get(&next).is_none_or(|c| next_cost < *c) {
+                    cost.insert(next, next_cost);
+                    previous.insert(next, node);
+                }
+            }
+        }
+    }
+    /// `best_path` as a header ready to be sent to `hops[1]`.
+    pub fn best_route(
+        &self,
+        topology: &Topology,
+        from: NodeId,
+        to: NodeId,
+    ) -> Option<SourceRoutingHeader> {
+        (self.best_path(topology, from, to)).map(|hops| SourceRoutingHeader { hop_index: 1, hops })
+    }
+}
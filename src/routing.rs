@@ -0,0 +1,11 @@
+use wg_2024::network::SourceRoutingHeader;
+
+/// Header of the answer to a packet (Ack, Nack, response): the part of `header` already
+/// traversed, reversed, ready to be sent to `hops[1]`.
+pub fn reversed_route(header: &SourceRoutingHeader) -> SourceRoutingHeader {
+    let traversed = header.hops.get(..=header.hop_index).unwrap_or(&header.hops);
+    SourceRoutingHeader {
+        hop_index: 1,
+        hops: traversed.iter().rev().copied().collect(),
+    }
+}
@@ -0,0 +1,315 @@
+use crate::network::{Network, NetworkEvent};
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use wg_2024::packet::PacketType;
+
+/// Timed experiment run against a spawned `Network`. Statements are separated by newlines
+/// or `;`, `#` starts a comment:
+/// ```text
+/// t=0 flood from client 10
+/// t=0 send 4 fragments of session 7 along 10 1 3 20
+/// t=200ms crash drone 3
+/// t=500ms set pdr of 5 to 0.4
+/// t=800ms recover drone 3
+/// expect all fragments of session 7 acked by t=2s
+/// expect all fragments of session 7 delivered by t=2s
+/// expect nack for session 7 by t=1s
+/// expect no nack for session 7
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub actions: Vec<(Duration, Action)>, // Sorted by time
+    pub expectations: Vec<Expectation>,
+}
+#[derive(Debug, Clone)]
+pub enum Action {
+    Flood {
+        from: NodeId,
+        flood_id: u64,
+    },
+    Send {
+        path: Vec<NodeId>,
+        count: u64,
+        session_id: u64,
+    },
+    Crash(NodeId),
+    Recover(NodeId),
+    SetPdr(NodeId, f32),
+}
+#[derive(Debug, Clone)]
+pub enum Expectation {
+    Acked { session_id: u64, by: Duration },
+    Delivered { session_id: u64, by: Duration },
+    Nacked { session_id: u64, by: Duration },
+    NoNack { session_id: u64 },
+}
+
+/// What the runner saw, `failures` is empty when every expectation held.
+#[derive(Debug, Clone, Default)]
+pub struct ScenarioReport {
+    pub passed: Vec<String>,
+    pub failures: Vec<String>,
+}
+impl ScenarioReport {
+    pub fn success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+// Per-session bookkeeping, timestamps are relative to the start of the run.
+#[derive(Default)]
+struct SessionTrace {
+    fragments: u64,
+    delivered: HashSet<u64>,
+    acked: HashSet<u64>,
+    all_delivered_at: Option<Duration>,
+    all_acked_at: Option<Duration>,
+    first_nack_at: Option<Duration>,
+}
+
+impl Scenario {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|er| format!("{}: {}", path, er))?;
+        Self::parse(&text)
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scenario = Scenario {
+            actions: Vec::new(),
+            expectations: Vec::new(),
+        };
+        let mut flood_id = 0;
+        for statement in text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split(';'))
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            let words: Vec<&str> = statement.split_whitespace().collect();
+            let error = || format!("Can't parse '{}'", statement);
+            if words[0] == "expect" {
+                let expectation = parse_expectation(&words[1..]).ok_or_else(error)?;
+                scenario.expectations.push(expectation);
+                continue;
+            }
+            let at = words[0]
+                .strip_prefix("t=")
+                .and_then(parse_time)
+                .ok_or_else(error)?;
+            // Node kind words are allowed for readability and otherwise ignored.
+            let words: Vec<&str> = (words[1..].iter().copied())
+                .filter(|w| !matches!(*w, "client" | "server" | "drone"))
+                .collect();
+            let action = match words.as_slice() {
+                ["flood", "from", id] => {
+                    flood_id += 1;
+                    Action::Flood {
+                        from: id.parse().map_err(|_| error())?,
+                        flood_id,
+                    }
+                }
+                ["send", count, "fragments", "of", "session", session, "along", path @ ..] => {
+                    Action::Send {
+                        path: (path.iter().map(|id| id.parse()))
+                            .collect::<Result<_, _>>()
+                            .map_err(|_| error())?,
+                        count: count.parse().map_err(|_| error())?,
+                        session_id: session.parse().map_err(|_| error())?,
+                    }
+                }
+                ["crash", id] => Action::Crash(id.parse().map_err(|_| error())?),
+                ["recover", id] => Action::Recover(id.parse().map_err(|_| error())?),
+                ["set", "pdr", "of", id, "to", pdr] => Action::SetPdr(
+                    id.parse().map_err(|_| error())?,
+                    pdr.parse().map_err(|_| error())?,
+                ),
+                _ => return Err(error()),
+            };
+            scenario.actions.push((at, action));
+        }
+        scenario.actions.sort_by_key(|(at, _)| *at);
+        Ok(scenario)
+    }
+
+    /// Executes the actions on schedule while polling the network, then checks expectations.
+    /// The run lasts until the last action or deadline, plus `grace` to let the network settle.
+    pub fn run(&self, network: &mut Network, grace: Duration) -> ScenarioReport {
+        let mut report = ScenarioReport::default();
+        let mut sessions: HashMap<u64, SessionTrace> = HashMap::new();
+        for (_, action) in &self.actions {
+            if let Action::Send {
+                count, session_id, ..
+            } = action
+            {
+                sessions.entry(*session_id).or_default().fragments += count;
+            }
+        }
+        let end = (self.actions.iter().map(|(at, _)| *at))
+            .chain(self.expectations.iter().filter_map(Expectation::deadline))
+            .max()
+            .unwrap_or_default()
+            + grace;
+
+        let start = Instant::now();
+        let mut next_action = 0;
+        while start.elapsed() <= end {
+            while let Some((at, action)) = self.actions.get(next_action) {
+                if *at > start.elapsed() {
+                    break;
+                }
+                if let Err(er) = Self::execute(network, action) {
+                    report
+                        .failures
+                        .push(format!("t={:?} {:?}: {}", at, action, er));
+                }
+                next_action += 1;
+            }
+            let now = start.elapsed();
+            for event in network.poll() {
+                let NetworkEvent::Received(id, packet) = event else {
+                    continue;
+                };
+                let trace = sessions.entry(packet.session_id).or_default();
+                match packet.pack_type {
+                    PacketType::MsgFragment(fragment)
+                        if packet.routing_header.hops.last() == Some(&id) =>
+                    {
+                        trace.delivered.insert(fragment.fragment_index);
+                    }
+                    PacketType::Ack(ack) => {
+                        trace.acked.insert(ack.fragment_index);
+                    }
+                    PacketType::Nack(_) => {
+                        trace.first_nack_at.get_or_insert(now);
+                    }
+                    _ => {}
+                }
+                if trace.fragments > 0 {
+                    if trace.delivered.len() as u64 >= trace.fragments {
+                        trace.all_delivered_at.get_or_insert(now);
+                    }
+                    if trace.acked.len() as u64 >= trace.fragments {
+                        trace.all_acked_at.get_or_insert(now);
+                    }
+                }
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        for expectation in &self.expectations {
+            let trace = sessions.get(&expectation.session_id());
+            let outcome = match expectation {
+                Expectation::Acked { by, .. } => Self::check_by(
+                    trace.and_then(|t| t.all_acked_at),
+                    *by,
+                    trace.map(|t| format!("{}/{} acked", t.acked.len(), t.fragments)),
+                ),
+                Expectation::Delivered { by, .. } => Self::check_by(
+                    trace.and_then(|t| t.all_delivered_at),
+                    *by,
+                    trace.map(|t| format!("{}/{} delivered", t.delivered.len(), t.fragments)),
+                ),
+                Expectation::Nacked { by, .. } => Self::check_by(
+                    trace.and_then(|t| t.first_nack_at),
+                    *by,
+                    Some("no nack".to_string()),
+                ),
+                Expectation::NoNack { .. } => match trace.and_then(|t| t.first_nack_at) {
+                    None => Ok(()),
+                    Some(at) => Err(format!("first nack at {:?}", at)),
+                },
+            };
+            match outcome {
+                Ok(()) => report.passed.push(format!("{:?}", expectation)),
+                Err(why) => report.failures.push(format!("{:?}: {}", expectation, why)),
+            }
+        }
+        report
+    }
+
+    fn execute(network: &mut Network, action: &Action) -> Result<(), String> {
+        match action {
+            Action::Flood { from, flood_id } => network.flood(*from, *flood_id),
+            Action::Send {
+                path,
+                count,
+                session_id,
+            } => network.send_fragments(path.clone(), *count, *session_id),
+            Action::Crash(id) => network.crash(*id),
+            Action::Recover(id) => network.recover(*id),
+            Action::SetPdr(id, pdr) => network.set_pdr(*id, *pdr),
+        }
+    }
+    fn check_by(
+        at: Option<Duration>,
+        by: Duration,
+        otherwise: Option<String>,
+    ) -> Result<(), String> {
+        match at {
+            Some(at) if at <= by => Ok(()),
+            Some(at) => Err(format!("happened late, at {:?}", at)),
+            None => Err(otherwise.unwrap_or("session never seen".to_string())),
+        }
+    }
+}
+
+impl Expectation {
+    fn session_id(&self) -> u64 {
+        match self {
+            Expectation::Acked { session_id, .. }
+            | Expectation::Delivered { session_id, .. }
+            | Expectation::Nacked { session_id, .. }
+            | Expectation::NoNack { session_id } => *session_id,
+        }
+    }
+    fn deadline(&self) -> Option<Duration> {
+        match self {
+            Expectation::Acked { by, .. }
+            | Expectation::Delivered { by, .. }
+            | Expectation::Nacked { by, .. } => Some(*by),
+            Expectation::NoNack { .. } => None,
+        }
+    }
+}
+
+fn parse_expectation(words: &[&str]) -> Option<Expectation> {
+    let deadline = |w: &str| w.strip_prefix("t=").and_then(parse_time);
+    match words {
+        ["all", "fragments", "of", "session", session, "acked", "by", by] => {
+            Some(Expectation::Acked {
+                session_id: session.parse().ok()?,
+                by: deadline(by)?,
+            })
+        }
+        ["all", "fragments", "of", "session", session, "delivered", "by", by] => {
+            Some(Expectation::Delivered {
+                session_id: session.parse().ok()?,
+                by: deadline(by)?,
+            })
+        }
+        ["nack", "for", "session", session, "by", by] => Some(Expectation::Nacked {
+            session_id: session.parse().ok()?,
+            by: deadline(by)?,
+        }),
+        ["no", "nack", "for", "session", session] => Some(Expectation::NoNack {
+            session_id: session.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+// "0", "200ms", "2s", "1.5s"
+fn parse_time(text: &str) -> Option<Duration> {
+    if let Some(ms) = text.strip_suffix("ms") {
+        ms.parse().ok().map(Duration::from_millis)
+    } else if let Some(s) = text.strip_suffix('s') {
+        s.parse()
+            .ok()
+            .and_then(|s| Duration::try_from_secs_f64(s).ok())
+    } else if text == "0" {
+        Some(Duration::ZERO)
+    } else {
+        None
+    }
+}
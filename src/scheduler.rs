@@ -0,0 +1,89 @@
+use crate::builder::Scheduling;
+use crate::status::QueueDelays;
+use std::collections::VecDeque;
+use std::time::Instant;
+use wg_2024::packet::{Packet, PacketType};
+
+/// Traffic classes of the drone's internal queue, in priority order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketClass {
+    Control,  // Ack, Nack, FloodResponse
+    Flood,    // FloodRequest
+    Fragment, // MsgFragment, the bulk traffic
+}
+
+impl PacketClass {
+    pub const ALL: [PacketClass; 3] = [Self::Control, Self::Flood, Self::Fragment];
+    pub fn of(packet: &Packet) -> Self {
+        match packet.pack_type {
+            PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => {
+                Self::Control
+            }
+            PacketType::FloodRequest(_) => Self::Flood,
+            PacketType::MsgFragment(_) => Self::Fragment,
+        }
+    }
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Queue the drone drains `packet_recv` into when `Scheduling` isn't Fifo, so that
+/// control packets don't wait behind the fragments received before them.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    queues: [VecDeque<(Instant, Packet)>; 3],
+    turn: usize, // Weighted: class being served
+    served: u32, // Weighted: packets served in this turn
+    delays: QueueDelays,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, packet: Packet) {
+        let class = PacketClass::of(&packet);
+        self.queues[class.index()].push_back((Instant::now(), packet));
+    }
+    /// Next packet to handle, with its class. Fifo is served as Priority.
+    pub fn pop(&mut self, scheduling: Scheduling) -> Option<(PacketClass, Packet)> {
+        let class = match scheduling {
+            Scheduling::Fifo | Scheduling::Priority => PacketClass::ALL
+                .into_iter()
+                .find(|c| !self.queues[c.index()].is_empty()),
+            Scheduling::Weighted {
+                control,
+                flood,
+                fragment,
+            } => self.next_weighted([control, flood, fragment]),
+        }?;
+        let (queued, packet) = self.queues[class.index()].pop_front()?;
+        self.delays.record(class, queued.elapsed());
+        Some((class, packet))
+    }
+    // Weighted round robin: up to `weights[class]` packets per class each round.
+    fn next_weighted(&mut self, weights: [u32; 3]) -> Option<PacketClass> {
+        for _ in 0..=PacketClass::ALL.len() {
+            if self.served < weights[self.turn] && !self.queues[self.turn].is_empty() {
+                self.served += 1;
+                return Some(PacketClass::ALL[self.turn]);
+            }
+            self.turn = (self.turn + 1) % PacketClass::ALL.len();
+            self.served = 0;
+        }
+        None
+    }
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+    pub fn delays(&self) -> QueueDelays {
+        self.delays
+    }
+    pub fn reset_delays(&mut self) {
+        self.delays = QueueDelays::default();
+    }
+}
@@ -0,0 +1,186 @@
+use crate::fragment::*;
+use crate::routing::reversed_route;
+use crossbeam_channel::{Receiver, Sender};
+use std::collections::{HashMap, HashSet};
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+/// Application logic plugged into a `Server`.
+pub trait MessageHandler: Send {
+    /// Called with every reassembled message, the returned bytes (if any) are sent back
+    /// to `source` along the reversed path of the request.
+    fn handle_message(&mut self, source: NodeId, session_id: u64, data: Vec<u8>)
+        -> Option<Vec<u8>>;
+}
+
+/// Answers every message with the message itself.
+pub struct EchoHandler;
+impl MessageHandler for EchoHandler {
+    fn handle_message(&mut self, _: NodeId, _: u64, data: Vec<u8>) -> Option<Vec<u8>> {
+        Some(data)
+    }
+}
+
+// Reply being sent, kept until every fragment is acked so Dropped Nacks can be retransmitted.
+struct Reply {
+    route: SourceRoutingHeader,
+    fragments: Vec<Fragment>,
+    acked: HashSet<u64>,
+}
+
+/// Reference server: acknowledges every fragment along the reversed routing header,
+/// reassembles messages per session and hands them to a `MessageHandler`.
+/// Flood requests are answered as a `NodeType::Server`, never forwarded.
+pub struct Server<H: MessageHandler> {
+    pub id: NodeId,
+    pub packet_recv: Receiver<Packet>,
+    pub packet_send: HashMap<NodeId, Sender<Packet>>,
+    handler: H,
+    reassembler: Reassembler,
+    replies: HashMap<u64, Reply>,
+    session_counter: u64,
+    logging_enabled: bool,
+}
+
+impl<H: MessageHandler> Server<H> {
+    pub fn new(
+        id: NodeId,
+        packet_recv: Receiver<Packet>,
+        packet_send: HashMap<NodeId, Sender<Packet>>,
+        handler: H,
+    ) -> Self {
+        Self {
+            id,
+            packet_recv,
+            packet_send,
+            handler,
+            reassembler: Reassembler::new(),
+            replies: HashMap::new(),
+            session_counter: 0,
+            logging_enabled: true,
+        }
+    }
+    pub fn logging_enabled(&mut self, log: bool) {
+        self.logging_enabled = log
+    }
+    /// Runs until every sender of `packet_recv` is dropped.
+    pub fn run(&mut self) {
+        while let Ok(packet) = self.packet_recv.recv() {
+            self.handle_packet(packet);
+        }
+    }
+    fn log<S: AsRef<str>>(&self, message: S) {
+        #[cfg(feature = "log")]
+        if self.logging_enabled {
+            println!("LeServer ID {} - {}", self.id, message.as_ref());
+        }
+    }
+
+    fn handle_packet(&mut self, packet: Packet) {
+        match packet.pack_type {
+            PacketType::MsgFragment(fragment) => {
+                let header = packet.routing_header;
+                let Some(source) = header.hops.first().copied() else {
+                    self.log("Fragment with empty routing header, can't ack it");
+                    return;
+                };
+                let back = reversed_route(&header);
+                self.send(Packet {
+                    session_id: packet.session_id,
+                    routing_header: back.clone(),
+                    pack_type: PacketType::Ack(Ack {
+                        fragment_index: fragment.fragment_index,
+                    }),
+                });
+                if let Some(data) = self.reassembler.insert(packet.session_id, &fragment) {
+                    self.log(format!(
+                        "Message from {} [SESSION ID: {}] complete, {} bytes",
+                        source,
+                        packet.session_id,
+                        data.len()
+                    ));
+                    if let Some(reply) =
+                        self.handler.handle_message(source, packet.session_id, data)
+                    {
+                        self.reply(back, reply);
+                    }
+                }
+            }
+            PacketType::Ack(ack) => {
+                if let Some(reply) = self.replies.get_mut(&packet.session_id) {
+                    reply.acked.insert(ack.fragment_index);
+                    if reply.acked.len() == reply.fragments.len() {
+                        self.replies.remove(&packet.session_id);
+                    }
+                }
+            }
+            PacketType::Nack(nack) => {
+                // Without a topology we can only retry on the same route.
+                let retry = match (&nack.nack_type, self.replies.get(&packet.session_id)) {
+                    (NackType::Dropped, Some(reply)) => reply
+                        .fragments
+                        .get(nack.fragment_index as usize)
+                        .map(|f| (reply.route.clone(), f.clone())),
+                    _ => None,
+                };
+                match retry {
+                    Some((route, fragment)) => self.send(Packet {
+                        session_id: packet.session_id,
+                        routing_header: route,
+                        pack_type: PacketType::MsgFragment(fragment),
+                    }),
+                    None => self.log(format!(
+                        "Nack {:?} [SESSION ID: {}] not recoverable",
+                        nack.nack_type, packet.session_id
+                    )),
+                }
+            }
+            PacketType::FloodRequest(mut flood) => {
+                flood.path_trace.push((self.id, NodeType::Server));
+                let hops: Vec<NodeId> = flood.path_trace.iter().rev().map(|(id, _)| *id).collect();
+                self.send(Packet {
+                    session_id: packet.session_id,
+                    routing_header: SourceRoutingHeader { hop_index: 1, hops },
+                    pack_type: PacketType::FloodResponse(FloodResponse {
+                        flood_id: flood.flood_id,
+                        path_trace: flood.path_trace,
+                    }),
+                });
+            }
+            PacketType::FloodResponse(_) => {
+                self.log("Unexpected FloodResponse, servers don't flood")
+            }
+        }
+    }
+    fn reply(&mut self, route: SourceRoutingHeader, data: Vec<u8>) {
+        self.session_counter += 1;
+        let session_id = ((self.id as u64) << 56) | self.session_counter;
+        let fragments = fragment_message(&data);
+        for fragment in &fragments {
+            self.send(Packet {
+                session_id,
+                routing_header: route.clone(),
+                pack_type: PacketType::MsgFragment(fragment.clone()),
+            });
+        }
+        self.replies.insert(
+            session_id,
+            Reply {
+                route,
+                fragments,
+                acked: HashSet::new(),
+            },
+        );
+    }
+    fn send(&self, packet: Packet) {
+        let next = packet.routing_header.current_hop();
+        match next.and_then(|next| self.packet_send.get(&next)) {
+            Some(channel) => {
+                if let Err(er) = channel.send(packet) {
+                    self.log(format!("{}", er));
+                }
+            }
+            None => self.log(format!("No channel towards {:?}", next)),
+        }
+    }
+}
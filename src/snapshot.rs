@@ -0,0 +1,56 @@
+use crate::builder::DroneConfig;
+use crate::config::NetworkConfig;
+use crate::network::NetworkStats;
+use crate::status::DroneState;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use wg_2024::network::NodeId;
+
+/// What a drone needs to carry on where it left: `DroneBuilder::from_snapshot` wires it
+/// to fresh channels. The neighbours in `state` are informative, the new wiring decides.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DroneSnapshot {
+    pub state: DroneState, // PDR, crashed, flood history, counters and config
+    pub rng: Option<ChaCha12Rng>, // Seeded drones keep their drop sequence
+    pub in_burst: bool,    // LossModel::Burst state
+}
+
+/// Checkpoint of a whole `Network`, see `Network::checkpoint` and `Network::restore`.
+/// Packets still in the channels aren't part of it, settle the network first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkCheckpoint {
+    pub config: NetworkConfig,
+    pub drone_config: DroneConfig,
+    pub links: BTreeMap<NodeId, Vec<NodeId>>, // Current links, crashes and recoveries included
+    pub drones: Vec<DroneSnapshot>,           // Running drones
+    pub crashed: Vec<(NodeId, f32)>,          // With their PDR, `recover` restarts them
+    pub stats: NetworkStats,
+}
+
+impl DroneSnapshot {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        save(self, path)
+    }
+    pub fn load(path: &str) -> Result<Self, String> {
+        load(path)
+    }
+}
+
+impl NetworkCheckpoint {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        save(self, path)
+    }
+    pub fn load(path: &str) -> Result<Self, String> {
+        load(path)
+    }
+}
+
+fn save<T: Serialize>(value: &T, path: &str) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(value).map_err(|er| format!("{}: {}", path, er))?;
+    std::fs::write(path, text).map_err(|er| format!("{}: {}", path, er))
+}
+fn load<T: for<'de> Deserialize<'de>>(path: &str) -> Result<T, String> {
+    let text = std::fs::read_to_string(path).map_err(|er| format!("{}: {}", path, er))?;
+    serde_json::from_str(&text).map_err(|er| format!("{}: {}", path, er))
+}
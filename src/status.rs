@@ -0,0 +1,140 @@
+use crate::builder::DroneConfig;
+use crate::control::LogLevel;
+use crate::scheduler::PacketClass;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wg_2024::network::NodeId;
+
+/// What a drone publishes about itself after every command it handles, and at most every
+/// 50ms while it handles packets (right away if it was quiet for longer).
+#[derive(Debug, Clone, Default)]
+pub struct DroneStatus {
+    pub pdr: f32,
+    pub crashed: bool,
+    pub neighbours: Vec<NodeId>,
+    pub flood_history: usize, // Floods remembered, see FloodRecord
+    pub queue_depth: usize,   // Packets waiting in packet_recv and the internal queue
+    pub counters: DroneCounters,
+    pub queue_delays: QueueDelays,
+}
+
+/// Counters kept by the drone since it started or the last `ControlCommand::ResetStats`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DroneCounters {
+    pub forwarded: u64, // Packets handed to a neighbour, Nacks and FloodResponses we built included
+    pub dropped: u64,   // Fragments dropped because of the PDR
+    pub nacked: NackCounters, // Nacks we generated
+    pub shortcuts: u64,
+    pub send_errors: u64, // Neighbour channel closed or missing
+    #[serde(default)]
+    pub invalid_routes: u64, // Packets refused by the route validation
+    #[serde(default)]
+    pub throttled_floods: u64, // New floods over the initiator's rate limit
+    #[serde(default)]
+    pub suppressed_floods: u64, // Duplicates answered instead of forwarded, see FloodDedup
+    #[serde(default)]
+    pub repeated_floods: u64, // Duplicates forwarded again by FloodDedup::Paths
+}
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NackCounters {
+    pub error_in_routing: u64,
+    pub destination_is_drone: u64,
+    pub dropped: u64,
+    pub unexpected_recipient: u64,
+}
+
+/// Everything a drone knows, dumped on demand with `ControlCommand::DumpState`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DroneState {
+    pub id: NodeId,
+    pub pdr: f32,
+    pub crashed: bool,
+    pub neighbours: Vec<NodeId>, // Who the drone believes it can send to
+    pub flood_history: BTreeMap<NodeId, Vec<FloodRecord>>, // Initiator -> floods seen, oldest first
+    pub counters: DroneCounters,
+    pub queue_depth: usize,
+    pub logging: bool,
+    pub log_level: LogLevel,
+    pub config: DroneConfig,
+    #[serde(default)]
+    pub queue_delays: QueueDelays,
+}
+
+/// Time packets spent in the internal queue, per class. Empty with `Scheduling::Fifo`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct QueueDelays {
+    pub control: ClassDelay,
+    pub flood: ClassDelay,
+    pub fragment: ClassDelay,
+}
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ClassDelay {
+    pub served: u64,
+    pub total_us: u64,
+    pub max_us: u64,
+}
+
+impl QueueDelays {
+    pub fn get(&self, class: PacketClass) -> ClassDelay {
+        match class {
+            PacketClass::Control => self.control,
+            PacketClass::Flood => self.flood,
+            PacketClass::Fragment => self.fragment,
+        }
+    }
+    pub fn record(&mut self, class: PacketClass, delay: Duration) {
+        let entry = match class {
+            PacketClass::Control => &mut self.control,
+            PacketClass::Flood => &mut self.flood,
+            PacketClass::Fragment => &mut self.fragment,
+        };
+        let us = delay.as_micros() as u64;
+        entry.served += 1;
+        entry.total_us += us;
+        entry.max_us = entry.max_us.max(us);
+    }
+}
+
+impl ClassDelay {
+    pub fn mean_us(&self) -> f64 {
+        if self.served == 0 {
+            0.0
+        } else {
+            self.total_us as f64 / self.served as f64
+        }
+    }
+}
+
+/// A flood the drone forwarded. With `FloodDedup::Wgl` and `FloodDedup::Paths` the session
+/// is the one of the first arrival and isn't part of the identity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FloodRecord {
+    pub flood_id: u64,
+    pub session_id: u64,
+    pub arrivals: usize, // Times we forwarded it
+}
+
+/// Shared between the drones of a network and whoever monitors them.
+#[derive(Debug, Clone, Default)]
+pub struct StatusBoard {
+    inner: Arc<Mutex<HashMap<NodeId, DroneStatus>>>,
+}
+
+impl StatusBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn publish(&self, id: NodeId, status: DroneStatus) {
+        if let Ok(mut board) = self.inner.lock() {
+            board.insert(id, status);
+        }
+    }
+    pub fn get(&self, id: NodeId) -> Option<DroneStatus> {
+        self.inner.lock().ok()?.get(&id).cloned()
+    }
+    pub fn all(&self) -> HashMap<NodeId, DroneStatus> {
+        self.inner.lock().map(|b| b.clone()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+/// Undirected picture of the network rebuilt from the path traces of FloodResponses.
+/// Every edge remembers when it was last confirmed, so stale links can be forgotten.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    nodes: HashMap<NodeId, NodeType>,
+    adjacency: HashMap<NodeId, HashSet<NodeId>>,
+    last_seen: HashMap<(NodeId, NodeId), Instant>, // Key is (min, max)
+}
+
+fn edge_key(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    (a.min(b), a.max(b))
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Merges the path trace of a FloodResponse, returns false for any other packet.
+    pub fn ingest_packet(&mut self, packet: &Packet) -> bool {
+        match &packet.pack_type {
+            PacketType::FloodResponse(response) => {
+                self.ingest(response);
+                true
+            }
+            _ => false,
+        }
+    }
+    pub fn ingest(&mut self, response: &FloodResponse) {
+        self.ingest_path(&response.path_trace);
+    }
+    /// Every consecutive pair of the trace is a link that existed when the flood passed.
+    pub fn ingest_path(&mut self, path_trace: &[(NodeId, NodeType)]) {
+        for (id, node_type) in path_trace {
+            self.add_node(*id, node_type.clone());
+        }
+        for pair in path_trace.windows(2) {
+            self.add_edge(pair[0].0, pair[1].0);
+        }
+    }
+    pub fn add_node(&mut self, id: NodeId, node_type: NodeType) {
+        self.nodes.insert(id, node_type);
+    }
+    pub fn add_edge(&mut self, a: NodeId, b: NodeId) {
+        if a == b {
+            return;
+        }
+        self.adjacency.entry(a).or_default().insert(b);
+        self.adjacency.entry(b).or_default().insert(a);
+        self.last_seen.insert(edge_key(a, b), Instant::now());
+    }
+    pub fn remove_edge(&mut self, a: NodeId, b: NodeId) {
+        if let Some(n) = self.adjacency.get_mut(&a) {
+            n.remove(&b);
+        }
+        if let Some(n) = self.adjacency.get_mut(&b) {
+            n.remove(&a);
+        }
+        self.last_seen.remove(&edge_key(a, b));
+    }
+    /// E.g. a crashed drone: the node and all its links go away.
+    pub fn remove_node(&mut self, id: NodeId) {
+        for n in self.adjacency.remove(&id).unwrap_or_default() {
+            if let Some(links) = self.adjacency.get_mut(&n) {
+                links.remove(&id);
+            }
+            self.last_seen.remove(&edge_key(id, n));
+        }
+        self.nodes.remove(&id);
+    }
+    /// Drops every edge not confirmed by a flood in the last `max_age`.
+    pub fn forget_older_than(&mut self, max_age: Duration) {
+        let stale: Vec<(NodeId, NodeId)> = (self.last_seen.iter())
+            .filter(|(_, seen)| seen.elapsed() > max_age)
+            .map(|(key, _)| *key)
+            .collect();
+        for (a, b) in stale {
+            self.remove_edge(a, b);
+        }
+    }
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.adjacency.clear();
+        self.last_seen.clear();
+    }
+
+    pub fn node_type(&self, id: NodeId) -> Option<&NodeType> {
+        self.nodes.get(&id)
+    }
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &NodeType)> {
+        self.nodes.iter().map(|(id, t)| (*id, t))
+    }
+    pub fn neighbours(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.adjacency.get(&id).into_iter().flatten().copied()
+    }
+    /// Edges as (a, b, last seen) with a < b.
+    pub fn edges(&self) -> impl Iterator<Item = (NodeId, NodeId, Instant)> + '_ {
+        self.last_seen.iter().map(|((a, b), seen)| (*a, *b, *seen))
+    }
+    pub fn edge_last_seen(&self, a: NodeId, b: NodeId) -> Option<Instant> {
+        self.last_seen.get(&edge_key(a, b)).copied()
+    }
+
+    /// Fewest-hops path from `from` to `to`, both included. Only drones are crossed,
+    /// clients and servers don't forward.
+    pub fn shortest_path(&self, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+        let mut previous: HashMap<NodeId, NodeId> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = vec![to];
+                while let Some(prev) = previous.get(path.last()?) {
+                    path.push(*prev);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if node != from && !self.is_drone(node) {
+                continue;
+            }
+            for next in self.neighbours(node) {
+                if next != from && !previous.contains_key(&next) {
+                    previous.insert(next, node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+    /// `shortest_path` as a header ready to be sent to `hops[1]`.
+    pub fn route(&self, from: NodeId, to: NodeId) -> Option<SourceRoutingHeader> {
+        self.shortest_path(from, to)
+            .map(|hops| SourceRoutingHeader { hop_index: 1, hops })
+    }
+    pub fn is_drone(&self, id: NodeId) -> bool {
+        matches!(self.nodes.get(&id), Some(NodeType::Drone))
+    }
+}
@@ -0,0 +1,131 @@
+use crate::codec::*;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use wg_2024::controller::*;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+// Biggest frame is a FloodRequest/Response with a long path trace, way under this.
+const MAX_DATAGRAM: usize = 65507;
+
+/// The four channels `wg_2024::drone::Drone::new` wants, backed by a UDP socket.
+pub struct DroneChannels {
+    pub controller_send: Sender<DroneEvent>,
+    pub controller_recv: Receiver<DroneCommand>,
+    pub packet_recv: Receiver<Packet>,
+    pub packet_send: HashMap<NodeId, Sender<Packet>>,
+}
+
+/// Adapter between a local UDP socket and the crossbeam channels used by the drone,
+/// so the drone itself runs unmodified in its own process.
+/// Incoming packets and commands are decoded on a reader thread, every neighbour sender
+/// and the event sender are drained by a small pump thread writing to the socket.
+/// Since UDP is connectionless a dead neighbour isn't noticed by the sender: packets
+/// just vanish, exactly like with a process that got killed.
+pub struct UdpTransport {
+    id: NodeId,
+    socket: Arc<UdpSocket>,
+}
+
+impl UdpTransport {
+    pub fn bind(id: NodeId, addr: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            id,
+            socket: Arc::new(UdpSocket::bind(addr)?),
+        })
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+    /// Spawns the reader and pump threads and hands back the drone side of the channels.
+    /// Events are sent to `controller` as `Frame::Event`, or discarded when it's `None`.
+    pub fn wire(
+        self,
+        neighbours: HashMap<NodeId, SocketAddr>,
+        controller: Option<SocketAddr>,
+    ) -> io::Result<DroneChannels> {
+        let (packet_tx, packet_recv) = unbounded::<Packet>();
+        let (command_tx, controller_recv) = unbounded::<DroneCommand>();
+        let (controller_send, event_rx) = unbounded::<DroneEvent>();
+
+        let packet_send = neighbours
+            .into_iter()
+            .map(|(id, addr)| (id, outbound(self.socket.clone(), addr)))
+            .collect();
+
+        let socket = self.socket.clone();
+        let id = self.id;
+        thread::Builder::new()
+            .name(format!("ledron-{}-events", id))
+            .spawn(move || {
+                for event in event_rx {
+                    if let (Some(addr), Ok(bytes)) =
+                        (controller, encode_frame(&Frame::Event(id, event)))
+                    {
+                        let _ = socket.send_to(&bytes, addr);
+                    }
+                }
+            })?;
+
+        let socket = self.socket;
+        thread::Builder::new()
+            .name(format!("ledron-{}-recv", id))
+            .spawn(move || {
+                let mut buf = vec![0u8; MAX_DATAGRAM];
+                loop {
+                    let len = match socket.recv_from(&mut buf) {
+                        Ok((len, _)) => len,
+                        Err(er) if er.kind() == io::ErrorKind::ConnectionReset => continue, // ICMP from a dead peer
+                        Err(_) => return,
+                    };
+                    let sent = match decode_frame(&buf[..len]) {
+                        Ok(Frame::Packet(packet)) => packet_tx.send(packet).is_ok(),
+                        Ok(Frame::Command(command)) => {
+                            let command = match command {
+                                RemoteCommand::AddSender(id, addr) => {
+                                    DroneCommand::AddSender(id, outbound(socket.clone(), addr))
+                                }
+                                RemoteCommand::RemoveSender(id) => DroneCommand::RemoveSender(id),
+                                RemoteCommand::SetPacketDropRate(pdr) => {
+                                    DroneCommand::SetPacketDropRate(pdr)
+                                }
+                                RemoteCommand::Crash => DroneCommand::Crash,
+                            };
+                            command_tx.send(command).is_ok()
+                        }
+                        // Nobody should send events to a drone, garbage is ignored as well.
+                        Ok(Frame::Event(..)) | Err(_) => true,
+                    };
+                    if !sent {
+                        return; // Drone is gone
+                    }
+                }
+            })?;
+
+        Ok(DroneChannels {
+            controller_send,
+            controller_recv,
+            packet_recv,
+            packet_send,
+        })
+    }
+}
+
+/// Returns a sender whose packets are written to `addr` by a pump thread.
+/// The thread ends when the sender (e.g. after a RemoveSender) is dropped.
+pub fn outbound(socket: Arc<UdpSocket>, addr: SocketAddr) -> Sender<Packet> {
+    let (tx, rx) = unbounded::<Packet>();
+    thread::spawn(move || {
+        for packet in rx {
+            // Packets that can't be encoded are lost like any datagram
+            if let Ok(bytes) = encode_frame(&Frame::Packet(packet)) {
+                let _ = socket.send_to(&bytes, addr);
+            }
+        }
+    });
+    tx
+}
@@ -0,0 +1,190 @@
+// Fragmentation, reversed routes, and a Client talking to an echo Server through drones.
+
+mod common;
+
+use common::line;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_2024::network::*;
+use LeDron_James::builder::DroneConfig;
+use LeDron_James::client::*;
+use LeDron_James::fragment::*;
+use LeDron_James::network::Network;
+use LeDron_James::routing::reversed_route;
+use LeDron_James::server::{EchoHandler, Server};
+
+fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7) as u8).collect()
+}
+
+#[test]
+fn fragments_carry_at_most_fragment_dsize_bytes() {
+    for (len, fragments) in [(0, 1), (1, 1), (128, 1), (129, 2), (256, 2), (1000, 8)] {
+        let data = message(len);
+        let split = fragment_message(&data);
+        assert_eq!(split.len(), fragments, "{} bytes", len);
+        for (i, fragment) in split.iter().enumerate() {
+            assert_eq!(fragment.fragment_index, i as u64);
+            assert_eq!(fragment.total_n_fragments, fragments as u64);
+        }
+        let lengths: usize = split.iter().map(|f| f.length as usize).sum();
+        assert_eq!(lengths, len);
+    }
+}
+
+#[test]
+fn reassembles_in_any_order_and_ignores_duplicates() {
+    let data = message(1000);
+    let mut fragments = fragment_message(&data);
+    fragments.reverse();
+    fragments.swap(2, 5);
+    let mut reassembler = Reassembler::new();
+    let (last, rest) = fragments.split_last().unwrap();
+    for fragment in rest {
+        assert_eq!(reassembler.insert(1, fragment), None);
+        assert_eq!(reassembler.insert(1, fragment), None); // A retransmission
+    }
+    assert_eq!(reassembler.pending_sessions(), 1);
+    assert_eq!(reassembler.insert(1, last), Some(data));
+    assert_eq!(reassembler.pending_sessions(), 0);
+    // A late duplicate starts nothing we could complete by mistake
+    assert_eq!(reassembler.insert(1, last), None);
+}
+
+#[test]
+fn sessions_are_reassembled_separately() {
+    let (a, b) = (message(300), message(10));
+    let mut reassembler = Reassembler::new();
+    let fragments_a = fragment_message(&a);
+    assert_eq!(reassembler.insert(7, &fragments_a[0]), None);
+    assert_eq!(reassembler.insert(8, &fragment_message(&b)[0]), Some(b));
+    assert_eq!(reassembler.insert(7, &fragments_a[2]), None);
+    assert_eq!(reassembler.insert(7, &fragments_a[1]), Some(a));
+    assert_eq!(
+        reassembler.insert(9, &fragment_message(&[])[0]),
+        Some(vec![])
+    );
+}
+
+#[test]
+fn fragments_that_can_never_complete_are_dropped() {
+    let mut reassembler = Reassembler::new();
+    let mut fragment = fragment_message(&message(10)).remove(0);
+    fragment.fragment_index = 1; // Of 1
+    assert_eq!(reassembler.insert(1, &fragment), None);
+    assert_eq!(reassembler.pending_sessions(), 0);
+}
+
+#[test]
+fn reversed_route_goes_back_from_the_current_hop() {
+    let header = SourceRoutingHeader {
+        hop_index: 2,
+        hops: vec![10, 1, 2, 3, 20],
+    };
+    assert_eq!(
+        reversed_route(&header),
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![2, 1, 10],
+        }
+    );
+    // An out of bounds hop index keeps the whole route
+    let header = SourceRoutingHeader {
+        hop_index: 9,
+        hops: vec![10, 1, 20],
+    };
+    assert_eq!(reversed_route(&header).hops, vec![20, 1, 10]);
+}
+
+// Client 10 and an echo server 20 on both ends of drones 1, 2 and 3.
+fn client_and_echo_server(pdr: f32) -> (Network, Sender<ClientCommand>, Receiver<ClientEvent>) {
+    let drone_config = DroneConfig {
+        logging: false,
+        seed: Some(11),
+        ..DroneConfig::default()
+    };
+    let mut network = Network::spawn_with(line(10, &[1, 2, 3], 20, pdr), drone_config).unwrap();
+    let (packet_recv, packet_send) = network.attach(20).unwrap();
+    let mut server = Server::new(20, packet_recv, packet_send, EchoHandler);
+    server.logging_enabled(false);
+    thread::spawn(move || server.run());
+    let (packet_recv, packet_send) = network.attach(10).unwrap();
+    let (command_send, command_recv) = unbounded();
+    let (event_send, events) = unbounded();
+    let mut client = Client::new(10, packet_recv, packet_send, command_recv, event_send);
+    client.logging_enabled(false);
+    thread::spawn(move || client.run());
+    (network, command_send, events)
+}
+
+// Client events until both the delivery and the echo came back, polling the network meanwhile.
+fn exchange(network: &mut Network, events: &Receiver<ClientEvent>) -> Vec<ClientEvent> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut seen = Vec::new();
+    while Instant::now() < deadline {
+        network.poll(); // Delivers the shortcuts
+        if let Ok(event) = events.recv_timeout(Duration::from_millis(5)) {
+            seen.push(event);
+            let delivered = seen
+                .iter()
+                .any(|e| matches!(e, ClientEvent::MessageDelivered { .. }));
+            let echoed = seen
+                .iter()
+                .any(|e| matches!(e, ClientEvent::MessageReceived { .. }));
+            if delivered && echoed {
+                break;
+            }
+        }
+    }
+    seen
+}
+
+fn assert_echoed(events: &[ClientEvent], data: &[u8]) {
+    assert!(
+        events.iter().any(|e| matches!(
+            e,
+            ClientEvent::MessageDelivered {
+                destination: 20,
+                ..
+            }
+        )),
+        "{:?}",
+        events
+    );
+    assert!(
+        events.iter().any(|e| match e {
+            ClientEvent::MessageReceived {
+                source, data: echo, ..
+            } => *source == 20 && echo == data,
+            _ => false,
+        }),
+        "{:?}",
+        events
+    );
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, ClientEvent::MessageFailed { .. })));
+}
+
+#[test]
+fn client_message_reaches_the_server_and_comes_back() {
+    let (mut network, commands, events) = client_and_echo_server(0.0);
+    let data = message(1000);
+    commands
+        .send(ClientCommand::SendMessage(20, data.clone()))
+        .unwrap();
+    assert_echoed(&exchange(&mut network, &events), &data);
+    assert_eq!(network.stats().dropped, 0);
+}
+
+#[test]
+fn dropped_fragments_are_retransmitted() {
+    let (mut network, commands, events) = client_and_echo_server(0.3);
+    let data = message(2000);
+    commands
+        .send(ClientCommand::SendMessage(20, data.clone()))
+        .unwrap();
+    assert_echoed(&exchange(&mut network, &events), &data);
+    assert!(network.stats().dropped > 0);
+}
@@ -0,0 +1,195 @@
+use std::net::SocketAddr;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::codec::*;
+
+fn packet(pack_type: PacketType) -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader {
+            hop_index: 2,
+            hops: vec![10, 1, 2, 20],
+        },
+        session_id: u64::MAX - 7,
+        pack_type,
+    }
+}
+
+fn every_packet_type() -> Vec<Packet> {
+    let mut data = [0; FRAGMENT_DSIZE];
+    data.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+    let path_trace = vec![
+        (10, NodeType::Client),
+        (1, NodeType::Drone),
+        (20, NodeType::Server),
+    ];
+    let mut packets = vec![
+        packet(PacketType::MsgFragment(Fragment {
+            fragment_index: 3,
+            total_n_fragments: 9,
+            length: 77,
+            data,
+        })),
+        packet(PacketType::Ack(Ack { fragment_index: 4 })),
+        packet(PacketType::FloodRequest(FloodRequest {
+            flood_id: 5,
+            initiator_id: 10,
+            path_trace: path_trace.clone(),
+        })),
+        packet(PacketType::FloodResponse(FloodResponse {
+            flood_id: 6,
+            path_trace,
+        })),
+    ];
+    for nack_type in [
+        NackType::ErrorInRouting(2),
+        NackType::DestinationIsDrone,
+        NackType::Dropped,
+        NackType::UnexpectedRecipient(7),
+    ] {
+        packets.push(packet(PacketType::Nack(Nack {
+            fragment_index: 8,
+            nack_type,
+        })));
+    }
+    packets
+}
+
+#[test]
+fn packets_round_trip() {
+    for packet in every_packet_type() {
+        let bytes = encode_packet(&packet).unwrap();
+        assert_eq!(decode_packet(&bytes).unwrap(), packet);
+    }
+}
+
+#[test]
+fn empty_routes_and_path_traces_round_trip() {
+    let mut packet = packet(PacketType::FloodResponse(FloodResponse {
+        flood_id: 1,
+        path_trace: vec![],
+    }));
+    packet.routing_header = SourceRoutingHeader {
+        hop_index: 0,
+        hops: vec![],
+    };
+    assert_eq!(
+        decode_packet(&encode_packet(&packet).unwrap()).unwrap(),
+        packet
+    );
+}
+
+#[test]
+fn events_round_trip() {
+    for packet in every_packet_type() {
+        for event in [
+            DroneEvent::PacketSent(packet.clone()),
+            DroneEvent::PacketDropped(packet.clone()),
+            DroneEvent::ControllerShortcut(packet.clone()),
+        ] {
+            let bytes = encode_frame(&Frame::Event(3, event.clone())).unwrap();
+            let Ok(Frame::Event(3, decoded)) = decode_frame(&bytes) else {
+                panic!("not an event of drone 3");
+            };
+            match (event, decoded) {
+                (DroneEvent::PacketSent(a), DroneEvent::PacketSent(b))
+                | (DroneEvent::PacketDropped(a), DroneEvent::PacketDropped(b))
+                | (DroneEvent::ControllerShortcut(a), DroneEvent::ControllerShortcut(b)) => {
+                    assert_eq!(a, b)
+                }
+                (a, b) => panic!("{:?} decoded as {:?}", a, b),
+            }
+        }
+    }
+}
+
+#[test]
+fn commands_round_trip() {
+    let v4: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+    let v6: SocketAddr = "[2001:db8::7]:9002".parse().unwrap();
+    for addr in [v4, v6] {
+        let bytes = encode_frame(&Frame::Command(RemoteCommand::AddSender(4, addr))).unwrap();
+        match decode_frame(&bytes) {
+            Ok(Frame::Command(RemoteCommand::AddSender(4, decoded))) => assert_eq!(decoded, addr),
+            other => panic!("{:?}", other),
+        }
+    }
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::RemoveSender(5))).unwrap();
+    assert!(matches!(
+        decode_frame(&bytes),
+        Ok(Frame::Command(RemoteCommand::RemoveSender(5)))
+    ));
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::SetPacketDropRate(0.25))).unwrap();
+    assert!(matches!(
+        decode_frame(&bytes),
+        Ok(Frame::Command(RemoteCommand::SetPacketDropRate(pdr))) if pdr == 0.25
+    ));
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::Crash)).unwrap();
+    assert!(matches!(
+        decode_frame(&bytes),
+        Ok(Frame::Command(RemoteCommand::Crash))
+    ));
+}
+
+#[test]
+fn truncated_input_is_an_error() {
+    for packet in every_packet_type() {
+        let bytes = encode_frame(&Frame::Packet(packet)).unwrap();
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(decode_frame(&bytes[..len]), Err(CodecError::Truncated)),
+                "{} of {} bytes",
+                len,
+                bytes.len()
+            );
+        }
+    }
+    let addr = "[::1]:9000".parse().unwrap();
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::AddSender(1, addr))).unwrap();
+    for len in 1..bytes.len() {
+        assert!(matches!(
+            decode_frame(&bytes[..len]),
+            Err(CodecError::Truncated)
+        ));
+    }
+}
+
+#[test]
+fn trailing_bytes_and_unknown_tags_are_errors() {
+    let mut bytes = encode_packet(&every_packet_type()[1]).unwrap();
+    bytes.push(0);
+    assert!(matches!(
+        decode_packet(&bytes),
+        Err(CodecError::TrailingBytes(1))
+    ));
+    assert!(matches!(
+        decode_frame(&[9]),
+        Err(CodecError::UnknownTag("frame", 9))
+    ));
+    assert!(matches!(
+        decode_frame(&[2, 0, 1, 5, 127, 0, 0, 1, 0, 0]),
+        Err(CodecError::UnknownTag("address family", 5))
+    ));
+}
+
+#[test]
+fn overlong_vectors_are_refused() {
+    let mut long_route = packet(PacketType::Ack(Ack { fragment_index: 0 }));
+    long_route.routing_header.hops = vec![1; u16::MAX as usize + 1];
+    assert!(matches!(
+        encode_packet(&long_route),
+        Err(CodecError::TooLong("hops", 65536))
+    ));
+    let long_trace = packet(PacketType::FloodResponse(FloodResponse {
+        flood_id: 0,
+        path_trace: vec![(1, NodeType::Drone); u16::MAX as usize + 1],
+    }));
+    assert!(matches!(
+        encode_frame(&Frame::Packet(long_trace)),
+        Err(CodecError::TooLong("path trace entries", 65536))
+    ));
+    // The longest ones still fit
+    long_route.routing_header.hops.pop();
+    let bytes = encode_packet(&long_route).unwrap();
+    assert_eq!(decode_packet(&bytes).unwrap(), long_route);
+}
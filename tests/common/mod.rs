@@ -0,0 +1,204 @@
+// Drone fixture shared by the integration tests, and by the benches through
+// `#[path = "../tests/common/mod.rs"] mod common;`. Each crate uses a part of it.
+#![allow(dead_code)]
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::{DroneBuilder, DroneConfig};
+use LeDron_James::config::{DroneEntry, EdgeEntry, NetworkConfig};
+use LeDron_James::Drone;
+
+pub const SESSION: u64 = 42;
+
+/// A drone linked to its neighbours, built but not running yet.
+pub struct Fixture {
+    pub drone: Drone,
+    pub packet_send: Sender<Packet>,
+    pub ends: Ends,
+}
+
+/// Channels the drone sends to, they have to outlive it.
+pub struct Ends {
+    pub neighbours: Vec<(NodeId, Receiver<Packet>)>,
+    pub events: Receiver<DroneEvent>,
+    pub commands: Sender<DroneCommand>, // The drone stops when it's dropped
+}
+
+/// What a drone sent to its neighbours and told the controller.
+#[derive(Debug)]
+pub struct Outcome {
+    pub sent: Vec<(NodeId, Packet)>,
+    pub events: Vec<DroneEvent>,
+}
+
+/// A fixture whose drone runs on its own thread.
+pub struct Running {
+    pub packet_send: Sender<Packet>,
+    pub ends: Ends,
+}
+
+/// Logging off, defaults otherwise.
+pub fn quiet() -> DroneConfig {
+    DroneConfig {
+        logging: false,
+        ..DroneConfig::default()
+    }
+}
+
+pub fn drone_with(id: NodeId, neighbours: &[NodeId], config: DroneConfig, pdr: f32) -> Fixture {
+    let (controller_send, events) = unbounded();
+    let (commands, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let mut builder = DroneBuilder::new(id)
+        .config(config)
+        .pdr(pdr)
+        .controller(controller_send, command_recv)
+        .packet_recv(packet_recv);
+    let mut receivers = Vec::new();
+    for neighbour in neighbours {
+        let (tx, rx) = unbounded();
+        builder = builder.neighbour(*neighbour, tx);
+        receivers.push((*neighbour, rx));
+    }
+    Fixture {
+        drone: builder.build().unwrap(),
+        packet_send,
+        ends: Ends {
+            neighbours: receivers,
+            events,
+            commands,
+        },
+    }
+}
+
+/// Drone 1 between 0 and 2, with the default config.
+pub fn drone_between_0_and_2() -> Fixture {
+    drone_with(1, &[0, 2], quiet(), 0.0)
+}
+
+impl Fixture {
+    pub fn crash(self) -> Self {
+        self.ends.commands.send(DroneCommand::Crash).unwrap();
+        self
+    }
+    /// Queues `packets` and closes the packet channel: `run` returns once they're all handled.
+    pub fn queue(self, packets: impl IntoIterator<Item = Packet>) -> (Drone, Ends) {
+        for packet in packets {
+            self.packet_send.send(packet).unwrap();
+        }
+        (self.drone, self.ends)
+    }
+    /// Handles `packets` on this thread.
+    pub fn run(self, packets: impl IntoIterator<Item = Packet>) -> Outcome {
+        let (mut drone, ends) = self.queue(packets);
+        drone.run();
+        ends.outcome()
+    }
+    pub fn spawn(self) -> Running {
+        let mut drone = self.drone;
+        thread::spawn(move || drone.run());
+        Running {
+            packet_send: self.packet_send,
+            ends: self.ends,
+        }
+    }
+}
+
+impl Ends {
+    /// Everything sent and reported so far.
+    pub fn outcome(&self) -> Outcome {
+        Outcome {
+            sent: (self.neighbours.iter())
+                .flat_map(|(id, rx)| rx.try_iter().map(|p| (*id, p)))
+                .collect(),
+            events: self.events.try_iter().collect(),
+        }
+    }
+}
+
+impl Running {
+    /// Sends `packet` and gives the drone some time to handle it.
+    pub fn handle(&self, packet: Packet) -> Outcome {
+        self.packet_send.send(packet).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        self.ends.outcome()
+    }
+}
+
+pub fn packet(hops: Vec<NodeId>, hop_index: usize, pack_type: PacketType) -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader { hop_index, hops },
+        session_id: SESSION,
+        pack_type,
+    }
+}
+
+pub fn fragment(fragment_index: u64, total_n_fragments: u64) -> PacketType {
+    PacketType::MsgFragment(Fragment {
+        fragment_index,
+        total_n_fragments,
+        length: FRAGMENT_DSIZE as u8,
+        data: [0xAB; FRAGMENT_DSIZE],
+    })
+}
+
+/// A FloodRequest of `initiator` that went through `path`, the initiator first.
+pub fn flood_request(flood_id: u64, initiator: NodeId, path: &[NodeId]) -> Packet {
+    let path_trace = (path.iter())
+        .map(|id| {
+            let node_type = if *id == initiator {
+                NodeType::Client
+            } else {
+                NodeType::Drone
+            };
+            (*id, node_type)
+        })
+        .collect();
+    packet(
+        vec![],
+        0,
+        PacketType::FloodRequest(FloodRequest {
+            flood_id,
+            initiator_id: initiator,
+            path_trace,
+        }),
+    )
+}
+
+pub fn is_nack(packet: &Packet) -> bool {
+    matches!(packet.pack_type, PacketType::Nack(_))
+}
+
+/// `client`, then `drones` in a line, then `server`, every drone with `pdr`.
+pub fn line(client: NodeId, drones: &[NodeId], server: NodeId, pdr: f32) -> NetworkConfig {
+    let drone = (drones.iter().enumerate())
+        .map(|(i, id)| DroneEntry {
+            id: *id,
+            connected_node_ids: [
+                i.checked_sub(1).map(|i| drones[i]),
+                drones.get(i + 1).copied(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            pdr,
+        })
+        .collect();
+    NetworkConfig {
+        drone,
+        client: vec![EdgeEntry {
+            id: client,
+            connected_drone_ids: vec![drones[0]],
+        }],
+        server: vec![EdgeEntry {
+            id: server,
+            connected_drone_ids: vec![drones[drones.len() - 1]],
+        }],
+        base_port: 47000,
+    }
+}
@@ -0,0 +1,130 @@
+// ControlCommands on drone 1 between 0 and 2, and their order with the DroneCommands.
+
+mod common;
+
+use common::*;
+use crossbeam_channel::Sender;
+use std::time::Duration;
+use wg_2024::controller::DroneCommand;
+use wg_2024::drone::Drone as _;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+use LeDron_James::control::{self, ControlCommand, LogLevel};
+use LeDron_James::status::{DroneState, DroneStatus};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+fn with_control() -> (Fixture, Sender<ControlCommand>) {
+    let mut fixture = drone_between_0_and_2();
+    let control = fixture.drone.control_channel();
+    (fixture, control)
+}
+
+fn query(control: &Sender<ControlCommand>) -> DroneStatus {
+    control::query_state(control, TIMEOUT).unwrap()
+}
+
+fn dump(control: &Sender<ControlCommand>) -> DroneState {
+    control::dump_state(control, TIMEOUT).unwrap()
+}
+
+fn to_2() -> Packet {
+    packet(vec![0, 1, 2], 1, fragment(0, 1))
+}
+
+#[test]
+fn query_state_replies_with_the_status() {
+    let (fixture, control) = with_control();
+    let running = fixture.spawn();
+    running.handle(to_2());
+    let status = query(&control);
+    assert_eq!(status.pdr, 0.0);
+    assert!(!status.crashed);
+    assert_eq!(status.neighbours, [0, 2]);
+    assert_eq!(status.counters.forwarded, 1);
+    assert_eq!(status.queue_depth, 0);
+}
+
+#[test]
+fn reset_stats_takes_the_counters_back_to_0() {
+    let (fixture, control) = with_control();
+    let running = fixture.spawn();
+    running.handle(to_2());
+    running.handle(packet(vec![0, 1, 7], 1, fragment(0, 1))); // Not a neighbour
+    assert_eq!(query(&control).counters.forwarded, 2); // The fragment and a Nack
+    control.send(ControlCommand::ResetStats).unwrap();
+    let counters = query(&control).counters;
+    assert_eq!(counters.forwarded, 0);
+    assert_eq!(counters.invalid_routes, 0);
+    assert_eq!(counters.nacked.error_in_routing, 0);
+}
+
+#[test]
+fn clear_flood_history_forgets_every_flood() {
+    let (fixture, control) = with_control();
+    let running = fixture.spawn();
+    let flood = flood_request(1, 10, &[10, 0]);
+    running.handle(flood.clone());
+    assert_eq!(dump(&control).flood_history[&10].len(), 1);
+    control.send(ControlCommand::ClearFloodHistory).unwrap();
+    assert!(dump(&control).flood_history.is_empty());
+    // Seen again for the first time: forwarded, not answered
+    let outcome = running.handle(flood);
+    let [(2, forwarded)] = outcome.sent.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert!(matches!(forwarded.pack_type, PacketType::FloodRequest(_)));
+}
+
+#[test]
+fn logging_and_log_level_are_switched() {
+    let (fixture, control) = with_control();
+    let _running = fixture.spawn();
+    assert_eq!(dump(&control).log_level, LogLevel::Debug);
+    control
+        .send(ControlCommand::SetLogLevel(LogLevel::Info))
+        .unwrap();
+    control.send(ControlCommand::SetLogging(true)).unwrap();
+    let state = dump(&control);
+    assert_eq!(state.log_level, LogLevel::Info);
+    assert!(state.logging);
+}
+
+#[test]
+fn invalid_loss_models_are_ignored() {
+    let (fixture, control) = with_control();
+    let running = fixture.spawn();
+    let nan = LossModel::Burst {
+        enter: f64::NAN,
+        leave: 0.5,
+        burst_pdr: f64::NAN,
+    };
+    control.send(ControlCommand::SetLossModel(nan)).unwrap();
+    // Still running and still uniform
+    let outcome = running.handle(to_2());
+    assert_eq!(outcome.sent.len(), 1, "{:?}", outcome);
+    assert_eq!(dump(&control).config.loss_model, LossModel::Uniform);
+    let burst = LossModel::Burst {
+        enter: 0.1,
+        leave: 0.5,
+        burst_pdr: 0.9,
+    };
+    control.send(ControlCommand::SetLossModel(burst)).unwrap();
+    assert_eq!(dump(&control).config.loss_model, burst);
+}
+
+#[test]
+fn drone_commands_go_before_control_commands() {
+    let (fixture, control) = with_control();
+    // Both pending before the drone starts, the control one sent first
+    let (reply, status) = crossbeam_channel::bounded(1);
+    control.send(ControlCommand::QueryState(reply)).unwrap();
+    let commands = &fixture.ends.commands;
+    commands.send(DroneCommand::SetPacketDropRate(0.7)).unwrap();
+    commands.send(DroneCommand::Crash).unwrap();
+    let (mut drone, _ends) = fixture.queue([]);
+    drone.run();
+    let status = status.try_recv().unwrap();
+    assert_eq!(status.pdr, 0.7);
+    assert!(status.crashed);
+}
@@ -0,0 +1,60 @@
+// FloodRequests left in the queue of drone 1, crashed between 0 and 2.
+
+mod common;
+
+use common::*;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+
+fn crashed(crashed_floods: CrashedFloodPolicy) -> Fixture {
+    let config = DroneConfig {
+        crashed_floods,
+        ..quiet()
+    };
+    drone_with(1, &[0, 2], config, 0.0).crash()
+}
+
+#[test]
+fn dropped_floods_are_only_reported() {
+    let flood = flood_request(1, 10, &[10, 0]);
+    let outcome = crashed(CrashedFloodPolicy::Drop).run([flood.clone()]);
+    // No Nack: it would follow the flood's routing header, which means nothing
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    let [DroneEvent::PacketDropped(dropped)] = outcome.events.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(*dropped, flood);
+}
+
+#[test]
+fn answered_floods_go_back_to_the_neighbour_they_came_from() {
+    let outcome = crashed(CrashedFloodPolicy::Respond).run([flood_request(1, 10, &[10, 0])]);
+    let [(0, response)] = outcome.sent.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(
+        response.routing_header,
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![1, 0, 10],
+        }
+    );
+    let PacketType::FloodResponse(flood) = &response.pack_type else {
+        panic!("{:?}", response);
+    };
+    assert_eq!(flood.flood_id, 1);
+    assert_eq!(
+        flood.path_trace,
+        [
+            (10, NodeType::Client),
+            (0, NodeType::Drone),
+            (1, NodeType::Drone)
+        ]
+    );
+    let [DroneEvent::PacketSent(sent)] = outcome.events.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(sent, response);
+}
@@ -0,0 +1,249 @@
+// DroneConfig from JSON and the environment, and what DroneBuilder refuses to build.
+
+use crossbeam_channel::unbounded;
+use std::collections::HashMap;
+use std::env;
+use wg_2024::drone::Drone as _;
+use LeDron_James::builder::*;
+use LeDron_James::Drone;
+
+// A file of its own per test, they run in parallel.
+fn write_config(name: &str, json: &str) -> String {
+    let path = env::temp_dir().join(format!("ledron-{}-{}.json", name, std::process::id()));
+    std::fs::write(&path, json).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn validate_refuses_every_setting_that_makes_no_sense() {
+    let weighted = |control, flood, fragment| Scheduling::Weighted {
+        control,
+        flood,
+        fragment,
+    };
+    let burst = |enter, leave, burst_pdr| LossModel::Burst {
+        enter,
+        leave,
+        burst_pdr,
+    };
+    let limit = |rate, burst| {
+        Some(FloodRateLimit {
+            rate,
+            burst,
+            excess: ExcessFloodPolicy::Drop,
+        })
+    };
+    let refused = [
+        DroneConfig {
+            queue_size: Some(0),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            flood_history_limit: Some(0),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            scheduling: weighted(0, 1, 1),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            scheduling: weighted(1, 0, 1),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            scheduling: weighted(1, 1, 0),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            flood_dedup: FloodDedup::Paths(0),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            flood_rate_limit: limit(0.0, 1),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            flood_rate_limit: limit(1.0, 0),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            loss_model: burst(1.5, 0.1, 0.5),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            loss_model: burst(0.1, -0.1, 0.5),
+            ..DroneConfig::default()
+        },
+        DroneConfig {
+            loss_model: burst(0.1, 0.1, f64::NAN),
+            ..DroneConfig::default()
+        },
+    ];
+    for config in refused {
+        assert!(config.validate().is_err(), "{:?}", config);
+    }
+    let fine = DroneConfig {
+        queue_size: Some(1),
+        flood_history_limit: Some(1),
+        scheduling: weighted(1, 1, 1),
+        flood_dedup: FloodDedup::Paths(1),
+        flood_rate_limit: limit(0.5, 1),
+        loss_model: burst(0.0, 1.0, 1.0),
+        ..DroneConfig::default()
+    };
+    assert_eq!(fine.validate(), Ok(()));
+    assert_eq!(DroneConfig::default().validate(), Ok(()));
+}
+
+#[test]
+fn settings_missing_from_the_file_are_the_defaults() {
+    let path = write_config("partial", r#"{ "seed": 42, "scheduling": "Priority" }"#);
+    let config = DroneConfig::from_file(&path).unwrap();
+    let expected = DroneConfig {
+        seed: Some(42),
+        scheduling: Scheduling::Priority,
+        ..DroneConfig::default()
+    };
+    assert_eq!(config, expected);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn bad_files_are_refused() {
+    for (name, json) in [
+        ("invalid", r#"{ "queue_size": 0 }"#),
+        ("malformed", r#"{ "seed": "#),
+        ("unknown-profile", r#"{ "profile": "Fast" }"#),
+    ] {
+        let path = write_config(name, json);
+        assert!(DroneConfig::from_file(&path).is_err(), "{}", json);
+        let _ = std::fs::remove_file(path);
+    }
+    assert!(DroneConfig::from_file("/nonexistent/drone.json").is_err());
+}
+
+// The environment is shared by the whole process: every from_env case is in this one test.
+#[test]
+fn the_environment_overrides_the_defaults_and_the_config_file() {
+    const VARS: [&str; 7] = [
+        "LEDRON_CONFIG",
+        "LEDRON_LOGGING",
+        "LEDRON_SEED",
+        "LEDRON_QUEUE_SIZE",
+        "LEDRON_FLOOD_HISTORY_LIMIT",
+        "LEDRON_PROFILE",
+        "LEDRON_CRASHED_FLOODS",
+    ];
+    let from_env = |vars: &[(&str, &str)]| {
+        VARS.iter().for_each(|name| env::remove_var(name));
+        vars.iter()
+            .for_each(|(name, value)| env::set_var(name, value));
+        let config = DroneConfig::from_env();
+        VARS.iter().for_each(|name| env::remove_var(name));
+        config
+    };
+    assert_eq!(from_env(&[]), Ok(DroneConfig::default()));
+    let config = from_env(&[
+        ("LEDRON_LOGGING", "false"),
+        ("LEDRON_SEED", "7"),
+        ("LEDRON_QUEUE_SIZE", "16"),
+        ("LEDRON_FLOOD_HISTORY_LIMIT", "4"),
+        ("LEDRON_PROFILE", "lossless"),
+        ("LEDRON_CRASHED_FLOODS", "Respond"),
+    ]);
+    let expected = DroneConfig {
+        logging: false,
+        seed: Some(7),
+        queue_size: Some(16),
+        flood_history_limit: Some(4),
+        profile: Profile::Lossless,
+        crashed_floods: CrashedFloodPolicy::Respond,
+        ..DroneConfig::default()
+    };
+    assert_eq!(config, Ok(expected));
+
+    for bad in [
+        ("LEDRON_SEED", "-1"),
+        ("LEDRON_QUEUE_SIZE", "lots"),
+        ("LEDRON_QUEUE_SIZE", "0"),
+        ("LEDRON_LOGGING", "yes"),
+        ("LEDRON_PROFILE", "Fast"),
+        ("LEDRON_CRASHED_FLOODS", "Ignore"),
+        ("LEDRON_CONFIG", "/nonexistent/drone.json"),
+    ] {
+        assert!(from_env(&[bad]).is_err(), "{:?}", bad);
+    }
+
+    // Settings only the file can carry, single variables still win
+    let path = write_config(
+        "env",
+        r#"{ "seed": 1, "flood_dedup": { "Paths": 3 }, "scheduling": "Priority",
+             "flood_rate_limit": { "rate": 2.0, "burst": 5 },
+             "loss_model": { "Burst": { "enter": 0.1, "leave": 0.5, "burst_pdr": 0.9 } } }"#,
+    );
+    let config = from_env(&[("LEDRON_CONFIG", &path), ("LEDRON_SEED", "9")]).unwrap();
+    assert_eq!(config.seed, Some(9));
+    assert_eq!(config.flood_dedup, FloodDedup::Paths(3));
+    assert_eq!(config.scheduling, Scheduling::Priority);
+    assert!(config.flood_rate_limit.is_some());
+    assert!(matches!(config.loss_model, LossModel::Burst { .. }));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn build_refuses_bad_drones() {
+    let builder = || {
+        let (controller_send, _) = unbounded();
+        let (_, command_recv) = unbounded();
+        let (_, packet_recv) = unbounded();
+        DroneBuilder::new(1)
+            .config(DroneConfig {
+                logging: false,
+                ..DroneConfig::default()
+            })
+            .controller(controller_send, command_recv)
+            .packet_recv(packet_recv)
+    };
+    assert!(builder().pdr(0.3).build().is_ok());
+    assert!(builder().pdr(0.0).build().is_ok());
+    assert!(builder().pdr(1.0).build().is_ok());
+    for pdr in [-0.1, 1.1, f32::NAN] {
+        assert!(builder().pdr(pdr).build().is_err(), "{}", pdr);
+    }
+    let (tx, _rx) = unbounded();
+    assert!(builder().neighbour(1, tx).build().is_err());
+    let invalid = DroneConfig {
+        queue_size: Some(0),
+        ..DroneConfig::default()
+    };
+    assert!(builder().config(invalid).build().is_err());
+    let (_, packet_recv) = unbounded();
+    assert!(DroneBuilder::new(1)
+        .packet_recv(packet_recv)
+        .build()
+        .is_err());
+    let (controller_send, _) = unbounded();
+    let (_, command_recv) = unbounded();
+    let no_packets = DroneBuilder::new(1).controller(controller_send, command_recv);
+    assert!(no_packets.build().is_err());
+}
+
+#[test]
+fn drone_new_uses_the_defaults() {
+    let (controller_send, _) = unbounded();
+    let (_, command_recv) = unbounded();
+    let (_, packet_recv) = unbounded();
+    let drone = Drone::new(
+        1,
+        controller_send,
+        command_recv,
+        packet_recv,
+        HashMap::new(),
+        0.2,
+    );
+    let state = drone.state();
+    assert_eq!(state.config, DroneConfig::default());
+    assert!(state.logging);
+    assert_eq!(state.pdr, 0.2);
+    assert!(!state.crashed);
+}
@@ -0,0 +1,95 @@
+// NetworkSnapshot of client 10 - drones 1 2 3 - server 20, after some traffic and a crash.
+
+mod common;
+
+use common::line;
+use std::time::Duration;
+use LeDron_James::export::{LinkState, NetworkSnapshot, NodeState};
+use LeDron_James::network::Network;
+
+const IDLE: Duration = Duration::from_millis(100);
+
+// 4 fragments delivered and acked, then one dropped by 1, then 3 crashes.
+fn snapshot() -> NetworkSnapshot {
+    let mut network = Network::spawn(line(10, &[1, 2, 3], 20, 0.0), false);
+    network.send_fragments(vec![10, 1, 2, 3, 20], 4, 7).unwrap();
+    network.settle(IDLE);
+    network.set_pdr(1, 1.0).unwrap();
+    network.send_fragments(vec![10, 1, 2, 3, 20], 1, 8).unwrap();
+    network.settle(IDLE);
+    network.crash(3).unwrap();
+    network.settle(IDLE);
+    NetworkSnapshot::capture(&network)
+}
+
+fn node(snapshot: &NetworkSnapshot, id: u8) -> &NodeState {
+    snapshot.nodes.iter().find(|n| n.id == id).unwrap()
+}
+
+fn link(snapshot: &NetworkSnapshot, a: u8, b: u8) -> &LinkState {
+    (snapshot.links.iter())
+        .find(|l| l.a == a && l.b == b)
+        .unwrap()
+}
+
+#[test]
+fn capture_has_crashes_links_and_drop_rates() {
+    let snapshot = snapshot();
+    let ids: Vec<u8> = snapshot.nodes.iter().map(|n| n.id).collect();
+    assert_eq!(ids, [1, 2, 3, 10, 20]);
+    assert!(node(&snapshot, 3).crashed);
+    assert!(!node(&snapshot, 2).crashed);
+    assert_eq!(node(&snapshot, 10).kind, "client");
+    assert_eq!(node(&snapshot, 10).pdr, None);
+    assert_eq!(node(&snapshot, 1).pdr, Some(1.0));
+
+    // Fragments one way, Acks the other, the links of 3 went down with it
+    let (one_two, two_three) = (link(&snapshot, 1, 2), link(&snapshot, 2, 3));
+    assert_eq!((one_two.a_to_b, one_two.b_to_a), (4, 4));
+    assert_eq!((two_three.a_to_b, two_three.b_to_a), (4, 4));
+    assert!(one_two.up);
+    assert!(!two_three.up);
+    assert!(!link(&snapshot, 3, 20).up);
+    assert_eq!(link(&snapshot, 1, 10).a_to_b, 5); // 4 Acks and the Nack
+
+    // 4 fragments, 4 Acks and a Nack sent, 1 fragment dropped
+    let one = node(&snapshot, 1);
+    assert_eq!((one.forwarded, one.dropped), (9, 1));
+    assert!((one.drop_rate - 0.1).abs() < 1e-9);
+    assert_eq!(node(&snapshot, 2).drop_rate, 0.0);
+}
+
+#[test]
+fn dot_dashes_links_down_and_fills_crashed_and_lossy_drones() {
+    let dot = snapshot().to_dot();
+    assert!(dot.starts_with("graph network {"), "{}", dot);
+    assert!(dot.contains("2 -- 3 ["), "{}", dot);
+    let line_of = |prefix: &str| {
+        (dot.lines())
+            .find(|l| l.trim_start().starts_with(prefix))
+            .unwrap_or_else(|| panic!("{} in {}", prefix, dot))
+            .to_string()
+    };
+    assert!(line_of("2 -- 3 ").contains("style=dashed"));
+    assert!(line_of("1 -- 2 ").contains("style=solid"));
+    let crashed = line_of("3 [");
+    assert!(crashed.contains("CRASHED") && crashed.contains("fillcolor=\"#e06060\""));
+    assert!(line_of("1 [").contains("style=filled"));
+    assert!(line_of("2 [").contains("style=solid"));
+}
+
+#[test]
+fn json_parses_back() {
+    let snapshot = snapshot();
+    let json: serde_json::Value = serde_json::from_str(&snapshot.to_json()).unwrap();
+    let nodes = json["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), snapshot.nodes.len());
+    let three = nodes.iter().find(|n| n["id"] == 3).unwrap();
+    assert_eq!(three["crashed"], true);
+    assert_eq!(three["kind"], "drone");
+    let links = json["links"].as_array().unwrap();
+    assert_eq!(links.len(), snapshot.links.len());
+    assert!(links
+        .iter()
+        .any(|l| l["a"] == 2 && l["b"] == 3 && l["up"] == false && l["a_to_b"] == 4));
+}
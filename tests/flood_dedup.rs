@@ -0,0 +1,121 @@
+// FloodDedup modes and the flood history of drone 1 between 0 and 2, floods of client 10.
+
+mod common;
+
+use common::*;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+use LeDron_James::status::DroneCounters;
+
+fn dedup(flood_dedup: FloodDedup, flood_history_limit: Option<usize>) -> Fixture {
+    let config = DroneConfig {
+        flood_dedup,
+        flood_history_limit,
+        ..quiet()
+    };
+    drone_with(1, &[0, 2], config, 0.0)
+}
+
+fn with_session(mut packet: Packet, session_id: u64) -> Packet {
+    packet.session_id = session_id;
+    packet
+}
+
+fn from_0(flood_id: u64) -> Packet {
+    flood_request(flood_id, 10, &[10, 0])
+}
+
+fn from_2(flood_id: u64) -> Packet {
+    flood_request(flood_id, 10, &[10, 2])
+}
+
+// What was forwarded (flood ids) and answered (hops of the FloodResponses), by neighbour.
+// Neighbour 0 first: Outcome doesn't keep the order between neighbours.
+#[derive(Debug, Default, PartialEq)]
+struct Sent {
+    forwarded: Vec<(NodeId, u64)>,
+    answered: Vec<(NodeId, Vec<NodeId>)>,
+}
+
+fn run(fixture: Fixture, packets: Vec<Packet>) -> (Sent, DroneCounters, Vec<u64>) {
+    let (mut drone, ends) = fixture.queue(packets);
+    drone.run();
+    let mut sent = Sent::default();
+    for (to, packet) in ends.outcome().sent {
+        match packet.pack_type {
+            PacketType::FloodRequest(flood) => sent.forwarded.push((to, flood.flood_id)),
+            PacketType::FloodResponse(_) => sent.answered.push((to, packet.routing_header.hops)),
+            _ => panic!("{:?}", packet),
+        }
+    }
+    let history = (drone.state().flood_history.get(&10).into_iter().flatten())
+        .map(|record| record.flood_id)
+        .collect();
+    (sent, drone.counters(), history)
+}
+
+#[test]
+fn wgl_answers_a_flood_seen_in_another_session() {
+    let floods = vec![from_0(1), with_session(from_2(1), 7)];
+    let (sent, counters, _) = run(dedup(FloodDedup::Wgl, None), floods);
+    assert_eq!(sent.forwarded, [(2, 1)]);
+    assert_eq!(sent.answered, [(2, vec![1, 2, 10])]);
+    assert_eq!(counters.suppressed_floods, 1);
+    assert_eq!(counters.repeated_floods, 0);
+}
+
+#[test]
+fn session_tells_sessions_apart() {
+    let floods = vec![from_0(1), with_session(from_2(1), 7), from_2(1)];
+    let (sent, counters, _) = run(dedup(FloodDedup::Session, None), floods);
+    assert_eq!(sent.forwarded, [(0, 1), (2, 1)]);
+    assert_eq!(sent.answered, [(2, vec![1, 2, 10])]);
+    assert_eq!(counters.suppressed_floods, 1);
+    assert_eq!(counters.repeated_floods, 0);
+}
+
+#[test]
+fn paths_forwards_the_first_arrivals_then_answers() {
+    let floods = vec![from_0(1), from_2(1), from_0(1), from_0(2)]; // Flood 1 arrives 3 times
+    let (sent, counters, _) = run(dedup(FloodDedup::Paths(2), None), floods);
+    assert_eq!(sent.forwarded, [(0, 1), (2, 1), (2, 2)]);
+    assert_eq!(sent.answered, [(0, vec![1, 0, 10])]);
+    assert_eq!(counters.repeated_floods, 1);
+    assert_eq!(counters.suppressed_floods, 1);
+}
+
+#[test]
+fn paths_answers_floods_that_already_went_through_us() {
+    // Our own copy coming back around a loop 1 - 2 - 1
+    let floods = vec![from_0(1), flood_request(1, 10, &[10, 0, 1, 2])];
+    let (sent, counters, _) = run(dedup(FloodDedup::Paths(3), None), floods);
+    assert_eq!(sent.forwarded, [(2, 1)]);
+    assert_eq!(sent.answered, [(2, vec![1, 2, 1, 0, 10])]);
+    assert_eq!(counters.repeated_floods, 0);
+    assert_eq!(counters.suppressed_floods, 1);
+}
+
+#[test]
+fn history_limit_forgets_the_oldest_floods() {
+    let floods = vec![from_0(1), from_0(2), from_0(3), from_2(1), from_2(3)];
+    let (sent, counters, history) = run(dedup(FloodDedup::Wgl, Some(2)), floods);
+    // Flood 1 was forgotten, so it's new again and pushes 2 out
+    assert_eq!(sent.forwarded, [(0, 1), (2, 1), (2, 2), (2, 3)]);
+    assert_eq!(sent.answered, [(2, vec![1, 2, 10])]);
+    assert_eq!(counters.suppressed_floods, 1);
+    assert_eq!(history, [3, 1]);
+}
+
+#[test]
+fn forgotten_floods_still_do_not_loop() {
+    let floods = vec![from_0(1), from_0(2), flood_request(1, 10, &[10, 0, 1, 2])];
+    for flood_dedup in [FloodDedup::Wgl, FloodDedup::Paths(2)] {
+        let (sent, counters, history) = run(dedup(flood_dedup, Some(1)), floods.clone());
+        assert_eq!(sent.forwarded, [(2, 1), (2, 2)], "{:?}", flood_dedup);
+        assert_eq!(sent.answered.len(), 1, "{:?}", flood_dedup);
+        assert_eq!(counters.suppressed_floods, 1, "{:?}", flood_dedup);
+        assert_eq!(history, [2], "{:?}", flood_dedup);
+    }
+}
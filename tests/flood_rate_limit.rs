@@ -0,0 +1,150 @@
+// Per-initiator token bucket of drone 1 between 0 and 2, floods of client 10 coming from 0.
+
+mod common;
+
+use common::*;
+use std::thread;
+use std::time::Duration;
+use wg_2024::controller::DroneEvent;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+use LeDron_James::status::DroneCounters;
+
+fn limited(rate: f64, burst: u32, excess: ExcessFloodPolicy, dedup: FloodDedup) -> Fixture {
+    let config = DroneConfig {
+        flood_rate_limit: Some(FloodRateLimit {
+            rate,
+            burst,
+            excess,
+        }),
+        flood_dedup: dedup,
+        ..quiet()
+    };
+    drone_with(1, &[0, 2], config, 0.0)
+}
+
+// Practically no refill during a test.
+fn burst_of(burst: u32, excess: ExcessFloodPolicy) -> Fixture {
+    limited(0.001, burst, excess, FloodDedup::Wgl)
+}
+
+fn from_0(flood_id: u64, initiator: NodeId) -> Packet {
+    flood_request(flood_id, initiator, &[initiator, 0])
+}
+
+fn run(fixture: Fixture, packets: Vec<Packet>) -> (Outcome, DroneCounters) {
+    let (mut drone, ends) = fixture.queue(packets);
+    drone.run();
+    (ends.outcome(), drone.counters())
+}
+
+fn floods_to(outcome: &Outcome, to: NodeId) -> Vec<u64> {
+    (outcome.sent.iter())
+        .filter(|(id, _)| *id == to)
+        .filter_map(|(_, p)| match &p.pack_type {
+            PacketType::FloodRequest(flood) => Some(flood.flood_id),
+            _ => None,
+        })
+        .collect()
+}
+
+fn dropped_floods(outcome: &Outcome) -> usize {
+    (outcome.events.iter())
+        .filter(|e| {
+            matches!(e, DroneEvent::PacketDropped(p)
+                if matches!(p.pack_type, PacketType::FloodRequest(_)))
+        })
+        .count()
+}
+
+#[test]
+fn an_initiator_gets_a_burst_of_floods_then_is_throttled() {
+    let floods = (1..=5).map(|id| from_0(id, 10)).collect();
+    let (outcome, counters) = run(burst_of(3, ExcessFloodPolicy::Drop), floods);
+    assert_eq!(floods_to(&outcome, 2), [1, 2, 3]);
+    assert_eq!(counters.throttled_floods, 2);
+    assert_eq!(dropped_floods(&outcome), 2);
+    // Dropped: 0 doesn't hear back
+    assert!(floods_to(&outcome, 0).is_empty());
+    assert!(outcome.sent.iter().all(|(id, _)| *id == 2));
+}
+
+#[test]
+fn every_initiator_has_its_own_bucket() {
+    let floods = vec![from_0(1, 10), from_0(2, 10), from_0(1, 11), from_0(2, 11)];
+    let (outcome, counters) = run(burst_of(1, ExcessFloodPolicy::Drop), floods);
+    assert_eq!(floods_to(&outcome, 2), [1, 1]);
+    assert_eq!(counters.throttled_floods, 2);
+}
+
+#[test]
+fn tokens_come_back_at_the_rate_up_to_the_burst() {
+    let running = limited(2.0, 1, ExcessFloodPolicy::Drop, FloodDedup::Wgl).spawn();
+    assert_eq!(floods_to(&running.handle(from_0(1, 10)), 2), [1]);
+    assert!(floods_to(&running.handle(from_0(2, 10)), 2).is_empty());
+    // Enough for a few tokens, the bucket holds one
+    thread::sleep(Duration::from_millis(1500));
+    assert_eq!(floods_to(&running.handle(from_0(3, 10)), 2), [3]);
+    assert!(floods_to(&running.handle(from_0(4, 10)), 2).is_empty());
+}
+
+#[test]
+fn excess_floods_can_be_answered() {
+    let floods = vec![from_0(1, 10), from_0(2, 10)];
+    let (outcome, counters) = run(burst_of(1, ExcessFloodPolicy::Respond), floods);
+    assert_eq!(floods_to(&outcome, 2), [1]);
+    assert_eq!(counters.throttled_floods, 1);
+    assert_eq!(dropped_floods(&outcome), 1); // Reported all the same
+    let to_0: Vec<&Packet> = (outcome.sent.iter())
+        .filter(|(id, _)| *id == 0)
+        .map(|(_, p)| p)
+        .collect();
+    let [response] = to_0[..] else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(response.routing_header.hops, [1, 0, 10]);
+    let PacketType::FloodResponse(response) = &response.pack_type else {
+        panic!("{:?}", response);
+    };
+    assert_eq!(response.flood_id, 2);
+    assert_eq!(response.path_trace.last(), Some(&(1, NodeType::Drone)));
+}
+
+#[test]
+fn repeat_arrivals_do_not_take_a_token() {
+    let from_2 = flood_request(1, 10, &[10, 2]);
+    // Wgl answers the repeat, Paths forwards it again: neither touches the bucket
+    for dedup in [FloodDedup::Wgl, FloodDedup::Paths(2)] {
+        let floods = vec![from_0(1, 10), from_2.clone(), from_0(2, 10), from_0(3, 10)];
+        let fixture = limited(0.001, 2, ExcessFloodPolicy::Drop, dedup);
+        let (outcome, counters) = run(fixture, floods);
+        assert_eq!(floods_to(&outcome, 2), [1, 2], "{:?}", dedup);
+        assert_eq!(counters.throttled_floods, 1, "{:?}", dedup);
+    }
+}
+
+#[test]
+fn throttled_floods_are_not_remembered() {
+    let floods = vec![from_0(1, 10), from_0(2, 10), flood_request(2, 10, &[10, 2])];
+    let (outcome, counters) = run(burst_of(1, ExcessFloodPolicy::Drop), floods);
+    assert_eq!(counters.throttled_floods, 2);
+    assert_eq!(counters.suppressed_floods, 0);
+    assert_eq!(dropped_floods(&outcome), 2);
+}
+
+#[test]
+fn limits_without_tokens_are_refused() {
+    for (rate, burst) in [(0.0, 2), (-1.0, 2), (f64::NAN, 2), (1.0, 0)] {
+        let config = DroneConfig {
+            flood_rate_limit: Some(FloodRateLimit {
+                rate,
+                burst,
+                excess: ExcessFloodPolicy::Drop,
+            }),
+            ..DroneConfig::default()
+        };
+        assert!(config.validate().is_err(), "{} {}", rate, burst);
+    }
+}
@@ -0,0 +1,302 @@
+// Every HookAction at every stage of the pipeline, on drone 1.
+
+mod common;
+
+use common::*;
+use std::sync::{Arc, Mutex};
+use wg_2024::controller::DroneEvent;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::pipeline::{HookAction, HookContext, PacketHook};
+
+#[derive(PartialEq)]
+enum Stage {
+    Routing,
+    Forward,
+    Nack,
+}
+
+// Runs `f` at `stage` only, lets everything through at the others.
+struct Hook<F> {
+    stage: Stage,
+    f: F,
+}
+
+impl<F: FnMut(&mut Packet) -> HookAction + Send> Hook<F> {
+    fn at(&mut self, stage: Stage, packet: &mut Packet) -> HookAction {
+        if self.stage == stage {
+            (self.f)(packet)
+        } else {
+            HookAction::Continue
+        }
+    }
+}
+
+impl<F: FnMut(&mut Packet) -> HookAction + Send> PacketHook for Hook<F> {
+    fn before_routing(&mut self, _: &HookContext, packet: &mut Packet) -> HookAction {
+        self.at(Stage::Routing, packet)
+    }
+    fn before_forward(
+        &mut self,
+        _: &HookContext,
+        packet: &mut Packet,
+        _: Option<NodeId>,
+    ) -> HookAction {
+        self.at(Stage::Forward, packet)
+    }
+    fn before_nack(&mut self, _: &HookContext, nack: &mut Packet) -> HookAction {
+        self.at(Stage::Nack, nack)
+    }
+}
+
+fn with_hook(
+    mut fixture: Fixture,
+    stage: Stage,
+    f: impl FnMut(&mut Packet) -> HookAction + Send + 'static,
+) -> Fixture {
+    fixture.drone.add_hook(Box::new(Hook { stage, f }));
+    fixture
+}
+
+// Drone 1 between 0 and 2, with a shortcut to 3.
+fn drone_with_shortcut() -> Fixture {
+    drone_with(1, &[0, 2, 3], quiet(), 0.0)
+}
+
+// Packets the hook was called with, and the hook itself always returning `action`.
+fn recording(
+    action: HookAction,
+) -> (
+    Arc<Mutex<Vec<Packet>>>,
+    impl FnMut(&mut Packet) -> HookAction + Send + 'static,
+) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let record = seen.clone();
+    (seen, move |packet: &mut Packet| {
+        record.lock().unwrap().push(packet.clone());
+        action
+    })
+}
+
+fn sent_events(outcome: &Outcome) -> Vec<&Packet> {
+    (outcome.events.iter())
+        .filter_map(|e| match e {
+            DroneEvent::PacketSent(p) => Some(p),
+            _ => None,
+        })
+        .collect()
+}
+
+fn dropped_nacks(outcome: &Outcome) -> Vec<&Packet> {
+    (outcome.events.iter())
+        .filter_map(|e| match e {
+            DroneEvent::PacketDropped(p) if is_nack(p) => Some(p),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn before_routing_drop_makes_the_packet_disappear() {
+    let fixture = with_hook(drone_between_0_and_2(), Stage::Routing, |_| {
+        HookAction::Drop
+    });
+    let outcome = fixture.run([packet(vec![0, 1, 2], 1, fragment(0, 1))]);
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    assert!(outcome.events.is_empty(), "{:?}", outcome);
+}
+
+#[test]
+fn before_routing_redirect_advances_the_route() {
+    let fixture = with_hook(drone_with_shortcut(), Stage::Routing, |_| {
+        HookAction::Redirect(3)
+    });
+    let outcome = fixture.run([
+        packet(vec![0, 1, 2, 4], 1, fragment(0, 1)),
+        packet(vec![0, 1], 1, fragment(1, 2)), // We're its destination
+    ]);
+    let headers: Vec<(NodeId, SourceRoutingHeader)> = (outcome.sent.iter())
+        .map(|(to, p)| (*to, p.routing_header.clone()))
+        .collect();
+    let expected = [
+        SourceRoutingHeader {
+            hop_index: 2,
+            hops: vec![0, 1, 3, 4],
+        },
+        SourceRoutingHeader {
+            hop_index: 2,
+            hops: vec![0, 1, 3],
+        },
+    ];
+    assert_eq!(
+        headers,
+        [(3, expected[0].clone()), (3, expected[1].clone())]
+    );
+    assert_eq!(sent_events(&outcome).len(), 2);
+}
+
+#[test]
+fn before_forward_continue_lets_the_hook_change_the_packet() {
+    let fixture = with_hook(drone_between_0_and_2(), Stage::Forward, |packet| {
+        packet.session_id += 1;
+        HookAction::Continue
+    });
+    let outcome = fixture.run([packet(vec![0, 1, 2], 1, fragment(0, 1))]);
+    let [(2, forwarded)] = outcome.sent.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(forwarded.session_id, SESSION + 1);
+    assert_eq!(forwarded.routing_header.hop_index, 2);
+}
+
+#[test]
+fn before_forward_drop_sends_nothing() {
+    let fixture = with_hook(drone_between_0_and_2(), Stage::Forward, |_| {
+        HookAction::Drop
+    });
+    let (mut drone, ends) = fixture.queue([packet(vec![0, 1, 2], 1, fragment(0, 1))]);
+    drone.run();
+    let outcome = ends.outcome();
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    assert!(outcome.events.is_empty(), "{:?}", outcome);
+    assert_eq!(drone.counters().forwarded, 0);
+}
+
+#[test]
+fn before_forward_redirect_replaces_the_next_hop() {
+    let fixture = with_hook(drone_with_shortcut(), Stage::Forward, |_| {
+        HookAction::Redirect(3)
+    });
+    let outcome = fixture.run([packet(vec![0, 1, 2, 4], 1, fragment(0, 1))]);
+    let [(3, redirected)] = outcome.sent.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(
+        redirected.routing_header,
+        SourceRoutingHeader {
+            hop_index: 2,
+            hops: vec![0, 1, 3, 4],
+        }
+    );
+}
+
+// Drone 7 isn't a neighbour: an ErrorInRouting Nack goes back to 0.
+fn not_routable() -> Packet {
+    packet(vec![0, 1, 7, 9], 1, fragment(3, 4))
+}
+
+#[test]
+fn before_nack_only_sees_the_nacks_we_build() {
+    let (seen, hook) = recording(HookAction::Continue);
+    let fixture = with_hook(drone_between_0_and_2(), Stage::Nack, hook);
+    let forwarded_nack = packet(
+        vec![2, 1, 0],
+        1,
+        PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        }),
+    );
+    let (mut drone, ends) = fixture.queue([not_routable(), forwarded_nack]);
+    drone.run();
+    let seen = seen.lock().unwrap();
+    let [nack] = seen.as_slice() else {
+        panic!("{:?}", seen);
+    };
+    assert_eq!(nack.routing_header.hops, [1, 0]);
+    assert_eq!(ends.outcome().sent.len(), 2);
+    assert_eq!(drone.counters().nacked.error_in_routing, 1);
+}
+
+#[test]
+fn before_nack_drop_keeps_the_nack_from_being_sent_or_counted() {
+    let fixture = with_hook(drone_between_0_and_2(), Stage::Nack, |_| HookAction::Drop);
+    let (mut drone, ends) = fixture.queue([not_routable()]);
+    drone.run();
+    let outcome = ends.outcome();
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    assert!(outcome.events.is_empty(), "{:?}", outcome);
+    assert_eq!(drone.counters().nacked.error_in_routing, 0);
+    // The fragment itself was still refused
+    assert_eq!(drone.counters().invalid_routes, 1);
+}
+
+#[test]
+fn before_nack_redirect_sends_the_nack_elsewhere() {
+    let fixture = with_hook(drone_between_0_and_2(), Stage::Nack, |_| {
+        HookAction::Redirect(2)
+    });
+    let outcome = fixture.run([not_routable()]);
+    let [(2, nack)] = outcome.sent.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert!(is_nack(nack));
+    assert_eq!(
+        nack.routing_header,
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![1, 2],
+        }
+    );
+}
+
+// Fragments whose sender can't be told apart: their Nack is only handed to the controller.
+fn unroutable_nacks() -> Vec<(Fixture, Packet)> {
+    vec![
+        // Out of bounds hop index and we aren't in the route
+        (
+            drone_between_0_and_2(),
+            packet(vec![0, 5, 9], 7, fragment(0, 1)),
+        ),
+        // Out of bounds hop index on a crashed drone
+        (
+            drone_between_0_and_2().crash(),
+            packet(vec![0, 1, 2], 9, fragment(0, 1)),
+        ),
+    ]
+}
+
+#[test]
+fn nacks_handed_to_the_controller_go_through_before_nack() {
+    for (fixture, fragment) in unroutable_nacks() {
+        let (seen, hook) = recording(HookAction::Continue);
+        let outcome = with_hook(fixture, Stage::Nack, hook).run([fragment]);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        assert_eq!(dropped_nacks(&outcome).len(), 1, "{:?}", outcome);
+    }
+    for (fixture, fragment) in unroutable_nacks() {
+        let fixture = with_hook(fixture, Stage::Nack, |_| HookAction::Drop);
+        let outcome = fixture.run([fragment]);
+        assert!(dropped_nacks(&outcome).is_empty(), "{:?}", outcome);
+        assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    }
+}
+
+#[test]
+fn nacks_of_mismatched_headers_go_through_before_nack() {
+    // Our current hop says 5: the Nacks' routes start from there, not from us
+    let mismatched = [
+        packet(vec![0, 5, 2], 1, fragment(0, 1)), // UnexpectedRecipient(5)
+        packet(vec![0, 5], 1, fragment(1, 2)),    // DestinationIsDrone, 5 being the last hop
+    ];
+    let (nacks, hook) = recording(HookAction::Continue);
+    let (forwarded, forward_hook) = recording(HookAction::Continue);
+    let mut fixture = with_hook(drone_between_0_and_2(), Stage::Nack, hook);
+    fixture.drone.add_hook(Box::new(Hook {
+        stage: Stage::Forward,
+        f: forward_hook,
+    }));
+    let (mut drone, ends) = fixture.queue(mismatched);
+    drone.run();
+    let nacks = nacks.lock().unwrap();
+    let hops: Vec<&[NodeId]> = (nacks.iter())
+        .map(|p| p.routing_header.hops.as_slice())
+        .collect();
+    assert_eq!(hops, [[5, 0], [5, 0]]);
+    assert!(forwarded.lock().unwrap().is_empty());
+    let nacked = drone.counters().nacked;
+    assert_eq!(nacked.unexpected_recipient, 1);
+    assert_eq!(nacked.destination_is_drone, 1);
+    assert_eq!(ends.outcome().sent.len(), 2);
+}
@@ -0,0 +1,326 @@
+// Protocol invariants clients rely on, checked on random routes, topologies and traffic.
+// Single drone properties run drone 1 on the test thread until its queue is empty.
+
+mod common;
+
+use common::{drone_with, is_nack, quiet, Outcome};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use proptest::prelude::*;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::thread;
+use std::time::Duration;
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::{DroneBuilder, DroneConfig};
+
+const ME: NodeId = 1;
+
+fn handle(neighbours: &BTreeSet<NodeId>, pdr: f32, crashed: bool, packets: Vec<Packet>) -> Outcome {
+    let neighbours: Vec<NodeId> = neighbours.iter().copied().collect();
+    let config = DroneConfig {
+        seed: Some(3),
+        ..quiet()
+    };
+    let fixture = drone_with(ME, &neighbours, config, pdr);
+    let fixture = if crashed { fixture.crash() } else { fixture };
+    fixture.run(packets)
+}
+
+fn built_by_us(packet: &Packet) -> bool {
+    packet.routing_header.hops.first() == Some(&ME)
+}
+
+fn neighbours() -> impl Strategy<Value = BTreeSet<NodeId>> {
+    prop::collection::btree_set(prop_oneof![Just(0u8), 2u8..8], 1..5)
+}
+
+// Routes through us, with a hop index usually pointing at us.
+fn routed() -> impl Strategy<Value = SourceRoutingHeader> {
+    (
+        prop::collection::vec(0u8..8, 0..4),
+        prop::collection::vec(0u8..8, 0..4),
+        prop::option::weighted(0.2, 0usize..10),
+    )
+        .prop_map(|(before, after, bogus_index)| {
+            let hop_index = bogus_index.unwrap_or(before.len());
+            let hops = before.into_iter().chain([ME]).chain(after).collect();
+            SourceRoutingHeader { hop_index, hops }
+        })
+}
+
+fn fragment() -> impl Strategy<Value = PacketType> {
+    any::<u64>().prop_map(|fragment_index| {
+        PacketType::MsgFragment(Fragment {
+            fragment_index,
+            total_n_fragments: fragment_index.saturating_add(1),
+            length: 1,
+            data: [0; FRAGMENT_DSIZE],
+        })
+    })
+}
+
+fn routed_packet(pack_type: impl Strategy<Value = PacketType>) -> impl Strategy<Value = Packet> {
+    (routed(), any::<u64>(), pack_type).prop_map(|(routing_header, session_id, pack_type)| Packet {
+        routing_header,
+        session_id,
+        pack_type,
+    })
+}
+
+// Every packet type but FloodRequests, which aren't source routed.
+fn any_routed_packet() -> impl Strategy<Value = Packet> {
+    routed_packet(prop_oneof![
+        fragment(),
+        any::<u64>().prop_map(|fragment_index| PacketType::Ack(Ack { fragment_index })),
+        any::<u64>().prop_map(|fragment_index| PacketType::Nack(Nack {
+            fragment_index,
+            nack_type: NackType::Dropped,
+        })),
+        any::<u64>().prop_map(|flood_id| PacketType::FloodResponse(FloodResponse {
+            flood_id,
+            path_trace: vec![],
+        })),
+    ])
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn packets_go_where_their_header_says(
+        neighbours in neighbours(),
+        pdr in 0.0f32..=1.0,
+        crashed in any::<bool>(),
+        packet in any_routed_packet(),
+    ) {
+        let outcome = handle(&neighbours, pdr, crashed, vec![packet.clone()]);
+        for (to, sent) in &outcome.sent {
+            let srh = &sent.routing_header;
+            prop_assert_eq!(srh.current_hop(), Some(*to));
+            if sent.pack_type != packet.pack_type {
+                // A Nack we built, back along the hops the packet took toward its source
+                prop_assert!(is_nack(sent));
+                prop_assert_eq!(srh.hop_index, 1);
+                let mut back = srh.hops.clone();
+                back.reverse();
+                prop_assert!(packet.routing_header.hops.starts_with(&back));
+                prop_assert_eq!(srh.hops.last(), packet.routing_header.hops.first());
+            } else {
+                prop_assert_eq!(&srh.hops, &packet.routing_header.hops);
+                prop_assert_eq!(srh.hop_index, packet.routing_header.hop_index + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn every_fragment_has_exactly_one_outcome(
+        neighbours in neighbours(),
+        pdr in 0.0f32..=1.0,
+        crashed in any::<bool>(),
+        packet in routed_packet(fragment()),
+    ) {
+        let outcome = handle(&neighbours, pdr, crashed, vec![packet]);
+        // Forwarded, Nacked to a neighbour or, when the Nack can't be sent, handed to the
+        // controller: dropped if nobody can be Nacked, shortcut if its next hop isn't linked
+        let forwarded = (outcome.sent.iter()).filter(|(_, p)| !is_nack(p)).count();
+        let nacked = (outcome.sent.iter()).filter(|(_, p)| is_nack(p)).count();
+        let reported = (outcome.events.iter())
+            .filter(|event| {
+                matches!(event, DroneEvent::PacketDropped(p) | DroneEvent::ControllerShortcut(p)
+                    if is_nack(p))
+            })
+            .count();
+        prop_assert_eq!(forwarded + nacked + reported, 1, "events: {:?}", outcome.events);
+    }
+
+    #[test]
+    fn floods_never_go_back_where_they_came_from(
+        neighbours in neighbours(),
+        crashed in any::<bool>(),
+        trace in prop::collection::vec(0u8..8, 0..4),
+        initiator in 0u8..8,
+        copies in 1usize..4,
+    ) {
+        let request = FloodRequest {
+            flood_id: 9,
+            initiator_id: initiator,
+            path_trace: trace.iter().map(|id| (*id, NodeType::Drone)).collect(),
+        };
+        let packet = Packet {
+            routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+            session_id: 5,
+            pack_type: PacketType::FloodRequest(request.clone()),
+        };
+        let outcome = handle(&neighbours, 0.0, crashed, vec![packet; copies]);
+        for (to, sent) in &outcome.sent {
+            match &sent.pack_type {
+                PacketType::FloodRequest(forwarded) => {
+                    prop_assert_ne!(Some(to), trace.last());
+                    let mut expected = request.path_trace.clone();
+                    expected.push((ME, NodeType::Drone));
+                    prop_assert_eq!(&forwarded.path_trace, &expected);
+                }
+                PacketType::FloodResponse(_) => {
+                    prop_assert!(built_by_us(sent));
+                    prop_assert_eq!(sent.routing_header.current_hop(), Some(*to));
+                    prop_assert_eq!(sent.routing_header.hops.last(), Some(&initiator));
+                }
+                other => prop_assert!(false, "unexpected {:?}", other),
+            }
+        }
+    }
+}
+
+// Drones 1..=n on their own threads, the client linked to drone 1 and the server to drone n.
+const CLIENT: NodeId = 100;
+const SERVER: NodeId = 101;
+
+struct Network {
+    inboxes: HashMap<NodeId, Sender<Packet>>,
+    links: HashMap<NodeId, Vec<NodeId>>,
+    client: Receiver<Packet>,
+    server: Receiver<Packet>,
+    events: Receiver<DroneEvent>,
+    _commands: Vec<Sender<DroneCommand>>,
+}
+
+// A spanning chain keeps every drone reachable, `extra` adds random links on top.
+fn network(drones: u8, extra: &[(u8, u8)], pdr: &[f32]) -> Network {
+    let mut links: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    let mut link = |a: NodeId, b: NodeId| {
+        if a != b && !links.get(&a).is_some_and(|l| l.contains(&b)) {
+            links.entry(a).or_default().push(b);
+            links.entry(b).or_default().push(a);
+        }
+    };
+    for id in 1..drones {
+        link(id, id + 1);
+    }
+    for (a, b) in extra {
+        link(a % drones + 1, b % drones + 1);
+    }
+    link(CLIENT, 1);
+    link(drones, SERVER);
+    let mut inboxes = HashMap::new();
+    let mut packet_recv = HashMap::new();
+    for id in (1..=drones).chain([CLIENT, SERVER]) {
+        let (tx, rx) = unbounded();
+        inboxes.insert(id, tx);
+        packet_recv.insert(id, rx);
+    }
+    let (controller_send, events) = unbounded();
+    let mut commands = Vec::new();
+    for id in 1..=drones {
+        let (command_send, command_recv) = unbounded();
+        let mut drone = DroneBuilder::new(id)
+            .config(DroneConfig {
+                logging: false,
+                seed: Some(id as u64),
+                ..DroneConfig::default()
+            })
+            .pdr(pdr[id as usize % pdr.len()])
+            .controller(controller_send.clone(), command_recv)
+            .packet_recv(packet_recv.remove(&id).unwrap())
+            .neighbours(
+                links[&id]
+                    .iter()
+                    .map(|n| (*n, inboxes[n].clone()))
+                    .collect(),
+            )
+            .build()
+            .unwrap();
+        thread::spawn(move || drone.run());
+        commands.push(command_send);
+    }
+    Network {
+        client: packet_recv.remove(&CLIENT).unwrap(),
+        server: packet_recv.remove(&SERVER).unwrap(),
+        inboxes,
+        links,
+        events,
+        _commands: commands,
+    }
+}
+
+impl Network {
+    // Shortest path from the client to the server.
+    fn route(&self) -> Vec<NodeId> {
+        let mut previous = HashMap::from([(CLIENT, CLIENT)]);
+        let mut queue = VecDeque::from([CLIENT]);
+        while let Some(node) = queue.pop_front() {
+            for next in &self.links[&node] {
+                if !previous.contains_key(next) {
+                    previous.insert(*next, node);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        let mut route = vec![SERVER];
+        while route.last() != Some(&CLIENT) {
+            route.push(previous[route.last().unwrap()]);
+        }
+        route.reverse();
+        route
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn fragments_arrive_or_are_nacked_once(
+        drones in 2u8..8,
+        extra in prop::collection::vec((any::<u8>(), any::<u8>()), 0..8),
+        pdr in prop::collection::vec(prop_oneof![Just(0.0f32), 0.0f32..=1.0], 1..4),
+        fragments in 1u64..20,
+        detour in prop::option::of((0usize..8, 1u8..8)),
+    ) {
+        let network = network(drones, &extra, &pdr);
+        let mut route = network.route();
+        if let Some((at, drone)) = detour {
+            // A hop that may not be linked, or already in the route
+            let at = 1 + at % (route.len() - 2);
+            route[at] = drone % drones + 1;
+        }
+        for fragment_index in 0..fragments {
+            network.inboxes[&route[1]].send(Packet {
+                routing_header: SourceRoutingHeader { hop_index: 1, hops: route.clone() },
+                session_id: 1,
+                pack_type: PacketType::MsgFragment(Fragment {
+                    fragment_index,
+                    total_n_fragments: fragments,
+                    length: 1,
+                    data: [0; FRAGMENT_DSIZE],
+                }),
+            }).unwrap();
+        }
+        let mut outcomes: HashMap<u64, usize> = HashMap::new();
+        loop {
+            crossbeam_channel::select! {
+                recv(network.server) -> packet => match packet.unwrap().pack_type {
+                    PacketType::MsgFragment(f) => *outcomes.entry(f.fragment_index).or_default() += 1,
+                    other => prop_assert!(false, "server got {:?}", other),
+                },
+                recv(network.client) -> packet => match packet.unwrap().pack_type {
+                    PacketType::Nack(n) => *outcomes.entry(n.fragment_index).or_default() += 1,
+                    other => prop_assert!(false, "client got {:?}", other),
+                },
+                // Nacks nobody could route, and Nacks the controller delivers for the drones
+                recv(network.events) -> event => match event.unwrap() {
+                    DroneEvent::PacketDropped(p) | DroneEvent::ControllerShortcut(p) => {
+                        if let PacketType::Nack(n) = p.pack_type {
+                            *outcomes.entry(n.fragment_index).or_default() += 1;
+                        }
+                    }
+                    DroneEvent::PacketSent(_) => {}
+                },
+                default(Duration::from_millis(200)) => break,
+            }
+        }
+        for fragment_index in 0..fragments {
+            prop_assert_eq!(outcomes.get(&fragment_index), Some(&1), "route {:?}", route);
+        }
+    }
+}
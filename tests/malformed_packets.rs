@@ -0,0 +1,80 @@
+// Packets that used to make the drone panic, found by fuzz/fuzz_targets/packet_handling.rs.
+// The drone runs on the test thread and returns once its queue is empty.
+
+mod common;
+
+use common::*;
+use wg_2024::controller::DroneEvent;
+use wg_2024::packet::*;
+
+// Drone 1 between 0 and 2 handles `packets`.
+fn handle(crashed: bool, packets: Vec<Packet>) -> Outcome {
+    let fixture = drone_between_0_and_2();
+    let fixture = if crashed { fixture.crash() } else { fixture };
+    fixture.run(packets)
+}
+
+#[test]
+fn nack_without_a_next_hop_goes_to_the_controller() {
+    // We aren't hops[0], the Nack back would be the route [5] alone
+    let Outcome { sent, events } = handle(false, vec![packet(vec![5, 1, 2], 0, fragment(0, 1))]);
+    assert!(sent.is_empty());
+    assert!(matches!(
+        events.as_slice(),
+        [DroneEvent::PacketDropped(Packet {
+            pack_type: PacketType::Nack(_),
+            ..
+        })]
+    ));
+}
+
+#[test]
+fn crashed_drone_with_out_of_bounds_hop_index() {
+    let Outcome { sent, events } = handle(
+        true,
+        vec![
+            packet(vec![0, 1, 2], 7, fragment(0, 1)),
+            packet(vec![], 0, fragment(0, 1)),
+        ],
+    );
+    // Nobody to Nack back to, the controller is told
+    assert!(sent.is_empty());
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|event| matches!(
+        event,
+        DroneEvent::PacketDropped(Packet {
+            pack_type: PacketType::Nack(_),
+            ..
+        })
+    )));
+}
+
+#[test]
+fn empty_routes_and_traces() {
+    let flood = |path_trace| {
+        PacketType::FloodRequest(FloodRequest {
+            flood_id: 1,
+            initiator_id: 1,
+            path_trace,
+        })
+    };
+    let sent = handle(
+        false,
+        vec![
+            packet(vec![], 0, fragment(0, 1)),
+            packet(vec![], 3, PacketType::Ack(Ack { fragment_index: 0 })),
+            packet(vec![], 0, flood(vec![])),
+            packet(vec![], 0, flood(vec![])), // A duplicate, answered
+            packet(
+                vec![1],
+                0,
+                PacketType::FloodResponse(FloodResponse {
+                    flood_id: 1,
+                    path_trace: vec![],
+                }),
+            ),
+        ],
+    )
+    .sent;
+    assert!(sent.is_empty());
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "metrics")]
+
+use LeDron_James::metrics::render;
+use LeDron_James::status::{DroneCounters, DroneStatus, NackCounters, StatusBoard};
+
+fn render_one(counters: DroneCounters) -> String {
+    let board = StatusBoard::new();
+    board.publish(
+        3,
+        DroneStatus {
+            counters,
+            ..DroneStatus::default()
+        },
+    );
+    render(&board)
+}
+
+#[test]
+fn drone_counts_are_counters() {
+    let counters = DroneCounters {
+        forwarded: 7,
+        nacked: NackCounters {
+            dropped: 2,
+            ..NackCounters::default()
+        },
+        ..DroneCounters::default()
+    };
+    let text = render_one(counters);
+    for name in [
+        "forwarded",
+        "dropped",
+        "shortcuts",
+        "send_errors",
+        "nacked",
+        "throttled_floods",
+    ] {
+        let kind = format!("# TYPE ledron_drone_{}_total counter\n", name);
+        assert!(text.contains(&kind), "{} in {}", name, text);
+    }
+    assert!(text.contains("ledron_drone_forwarded_total{drone=\"3\"} 7\n"));
+    assert!(text.contains("ledron_drone_nacked_total{drone=\"3\",type=\"Dropped\"} 2\n"));
+    assert!(text.contains("# TYPE ledron_drone_queue_depth gauge\n"));
+}
+
+#[test]
+fn queue_delays_are_a_summary_by_class() {
+    let text = render_one(DroneCounters::default());
+    assert!(text.contains("# TYPE ledron_drone_queue_delay_seconds summary\n"));
+    assert!(
+        text.contains("ledron_drone_queue_delay_seconds_count{drone=\"3\",class=\"Fragment\"} 0\n")
+    );
+}
@@ -0,0 +1,80 @@
+// Network::crash, recover and shutdown on client 10 - drones 1 2 3 - server 20.
+
+mod common;
+
+use common::line;
+use crossbeam_channel::bounded;
+use std::thread;
+use std::time::Duration;
+use wg_2024::packet::*;
+use LeDron_James::network::{Network, NetworkEvent};
+
+const IDLE: Duration = Duration::from_millis(100);
+
+fn network() -> Network {
+    Network::spawn(line(10, &[1, 2, 3], 20, 0.0), false)
+}
+
+// What clients and servers got: (node, packet type) in arrival order.
+fn received(events: &[NetworkEvent]) -> Vec<(u8, String)> {
+    (events.iter())
+        .filter_map(|event| match event {
+            NetworkEvent::Received(id, packet) => {
+                let kind = match &packet.pack_type {
+                    PacketType::MsgFragment(_) => "Fragment".to_string(),
+                    PacketType::Ack(_) => "Ack".to_string(),
+                    PacketType::Nack(nack) => format!("{:?}", nack.nack_type),
+                    other => format!("{:?}", other),
+                };
+                Some((*id, kind))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn a_crash_removes_the_links_of_the_drone() {
+    let mut network = network();
+    network.crash(2).unwrap();
+    assert!(network.is_crashed(2));
+    assert!(network.neighbours_of(2).is_empty());
+    assert_eq!(network.neighbours_of(1), [10]);
+    assert_eq!(network.neighbours_of(3), [20]);
+    assert!(network.crash(2).is_err());
+
+    network.send_fragments(vec![10, 1, 2, 3, 20], 1, 7).unwrap();
+    let events = network.settle(IDLE);
+    assert_eq!(received(&events), [(10, "ErrorInRouting(2)".to_string())]);
+}
+
+#[test]
+fn a_recover_puts_the_links_back() {
+    let mut network = network();
+    assert!(network.recover(2).is_err()); // Not crashed
+    network.crash(2).unwrap();
+    network.settle(IDLE);
+    network.recover(2).unwrap();
+    assert!(!network.is_crashed(2));
+    assert_eq!(network.neighbours_of(2), [1, 3]);
+    assert_eq!(network.neighbours_of(1), [10, 2]);
+    assert_eq!(network.neighbours_of(3), [20, 2]);
+
+    network.send_fragments(vec![10, 1, 2, 3, 20], 1, 7).unwrap();
+    let events = network.settle(IDLE);
+    assert_eq!(
+        received(&events),
+        [(20, "Fragment".to_string()), (10, "Ack".to_string())]
+    );
+}
+
+#[test]
+fn shutdown_returns_with_crashed_and_recovered_drones() {
+    let mut network = network();
+    network.crash(1).unwrap();
+    network.crash(3).unwrap();
+    network.recover(3).unwrap();
+    let (done, finished) = bounded(1);
+    thread::spawn(move || done.send(network.shutdown()));
+    assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok());
+}
@@ -0,0 +1,75 @@
+// Drone 1 between 0 and 2 sending to node 5, which it isn't linked to.
+
+mod common;
+
+use common::*;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+use LeDron_James::pipeline::{HookAction, HookContext, PacketHook};
+
+#[test]
+fn a_nack_back_to_an_unlinked_sender_is_shortcut() {
+    // 7 isn't a neighbour either: the ErrorInRouting Nack has to go back to 5
+    let outcome = drone_between_0_and_2().run([packet(vec![5, 1, 7], 1, fragment(3, 4))]);
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    let shortcut: Vec<&Packet> = (outcome.events.iter())
+        .filter_map(|e| match e {
+            DroneEvent::ControllerShortcut(p) => Some(p),
+            _ => None,
+        })
+        .collect();
+    let [nack] = shortcut[..] else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(nack.routing_header.hops, [1, 5]);
+    assert!(matches!(
+        nack.pack_type,
+        PacketType::Nack(Nack {
+            fragment_index: 3,
+            nack_type: NackType::ErrorInRouting(7),
+        })
+    ));
+}
+
+#[test]
+fn a_crashed_drone_shortcuts_its_flood_response() {
+    let config = DroneConfig {
+        crashed_floods: CrashedFloodPolicy::Respond,
+        ..quiet()
+    };
+    let fixture = drone_with(1, &[0, 2], config, 0.0).crash();
+    let outcome = fixture.run([flood_request(1, 10, &[10, 5])]);
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    let [DroneEvent::ControllerShortcut(response)] = outcome.events.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(response.routing_header.hops, [1, 5, 10]);
+    let PacketType::FloodResponse(response) = &response.pack_type else {
+        panic!("{:?}", response);
+    };
+    assert_eq!(response.path_trace.last(), Some(&(1, NodeType::Drone)));
+}
+
+#[test]
+fn fragments_and_floods_are_still_dropped() {
+    // Redirected to 5 by a hook: nothing but the controller can tell
+    let mut fixture = drone_between_0_and_2();
+    fixture.drone.add_hook(Box::new(ToFive));
+    let outcome = fixture.run([
+        packet(vec![0, 1, 2], 1, fragment(0, 1)),
+        flood_request(1, 10, &[10, 0]),
+    ]);
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    assert_eq!(outcome.events.len(), 2, "{:?}", outcome);
+    assert!((outcome.events.iter()).all(|e| matches!(e, DroneEvent::PacketDropped(_))));
+}
+
+struct ToFive;
+
+impl PacketHook for ToFive {
+    fn before_forward(&mut self, _: &HookContext, _: &mut Packet, _: Option<NodeId>) -> HookAction {
+        HookAction::Redirect(5)
+    }
+}
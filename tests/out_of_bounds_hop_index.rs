@@ -0,0 +1,122 @@
+// A drone receiving a packet whose hop_index is past the end of its route.
+// Drone 1 sits between 0 and 2, node 9 exists but isn't its neighbour.
+
+mod common;
+
+use common::*;
+use wg_2024::controller::DroneEvent;
+use wg_2024::packet::*;
+
+#[test]
+fn fragment_is_nacked_back_to_the_source() {
+    let drone = drone_between_0_and_2().spawn();
+    let Outcome { sent, events } = drone.handle(packet(vec![10, 0, 1, 2, 20], 9, fragment(3, 5)));
+
+    assert_eq!(sent.len(), 1);
+    let (to, nack) = &sent[0];
+    assert_eq!(*to, 0);
+    assert_eq!(nack.session_id, SESSION);
+    assert_eq!(nack.routing_header.hops, vec![1, 0, 10]);
+    assert_eq!(nack.routing_header.hop_index, 1);
+    match &nack.pack_type {
+        PacketType::Nack(n) => {
+            assert_eq!(n.fragment_index, 3);
+            assert_eq!(n.nack_type, NackType::UnexpectedRecipient(1));
+        }
+        other => panic!("expected a Nack, got {:?}", other),
+    }
+    assert!(matches!(events.as_slice(), [DroneEvent::PacketSent(_)]));
+}
+
+#[test]
+fn fragment_goes_to_the_controller_when_the_route_is_ambiguous() {
+    let drone = drone_between_0_and_2().spawn();
+    for hops in [
+        vec![10, 0, 1, 2, 1, 20], // We are in there twice
+        vec![10, 0, 2, 20],       // We aren't in there at all
+        vec![1, 2, 20],           // We'd be the source
+        vec![10, 9, 1, 2, 20],    // The hop before us isn't a neighbour
+    ] {
+        let Outcome { sent, events } = drone.handle(packet(hops.clone(), 17, fragment(3, 5)));
+        assert!(sent.is_empty(), "{:?}: sent {:?}", hops, sent);
+        match events.as_slice() {
+            [DroneEvent::PacketDropped(nack)] => {
+                assert_eq!(nack.routing_header.hops, hops);
+                assert!(matches!(
+                    nack.pack_type,
+                    PacketType::Nack(Nack {
+                        fragment_index: 3,
+                        nack_type: NackType::UnexpectedRecipient(1),
+                    })
+                ));
+            }
+            other => panic!("{:?}: expected a PacketDropped, got {:?}", hops, other),
+        }
+    }
+}
+
+#[test]
+fn ack_is_shortcut_to_the_controller() {
+    let drone = drone_between_0_and_2().spawn();
+    let ack = packet(
+        vec![20, 2, 1, 0, 10],
+        9,
+        PacketType::Ack(Ack { fragment_index: 1 }),
+    );
+    let Outcome { sent, events } = drone.handle(ack.clone());
+    assert!(sent.is_empty());
+    assert!(matches!(events.as_slice(), [DroneEvent::ControllerShortcut(p)] if *p == ack));
+}
+
+#[test]
+fn nack_is_shortcut_to_the_controller() {
+    let drone = drone_between_0_and_2().spawn();
+    let nack = packet(
+        vec![20, 2, 1, 0, 10],
+        9,
+        PacketType::Nack(Nack {
+            fragment_index: 1,
+            nack_type: NackType::Dropped,
+        }),
+    );
+    let Outcome { sent, events } = drone.handle(nack.clone());
+    assert!(sent.is_empty());
+    assert!(matches!(events.as_slice(), [DroneEvent::ControllerShortcut(p)] if *p == nack));
+}
+
+#[test]
+fn flood_response_is_shortcut_to_the_controller() {
+    let drone = drone_between_0_and_2().spawn();
+    let response = packet(
+        vec![20, 2, 1, 0, 10],
+        9,
+        PacketType::FloodResponse(FloodResponse {
+            flood_id: 5,
+            path_trace: vec![
+                (10, NodeType::Client),
+                (0, NodeType::Drone),
+                (1, NodeType::Drone),
+                (2, NodeType::Drone),
+                (20, NodeType::Server),
+            ],
+        }),
+    );
+    let Outcome { sent, events } = drone.handle(response.clone());
+    assert!(sent.is_empty());
+    assert!(matches!(events.as_slice(), [DroneEvent::ControllerShortcut(p)] if *p == response));
+}
+
+#[test]
+fn flood_request_ignores_the_hop_index() {
+    let drone = drone_between_0_and_2().spawn();
+    let mut request = flood_request(5, 10, &[10, 0]);
+    request.routing_header.hop_index = 9;
+    let sent = drone.handle(request).sent;
+    assert_eq!(sent.len(), 1);
+    let (to, forwarded) = &sent[0];
+    assert_eq!(*to, 2);
+    match &forwarded.pack_type {
+        PacketType::FloodRequest(f) => assert_eq!(f.path_trace.last(), Some(&(1, NodeType::Drone))),
+        other => panic!("expected a FloodRequest, got {:?}", other),
+    }
+}
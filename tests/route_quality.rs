@@ -0,0 +1,163 @@
+// Client 10 reaches server 20 through drone 1, or the long way through drones 2 and 3.
+
+use std::thread;
+use std::time::Duration;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::fragment::fragment_message;
+use LeDron_James::route_quality::RouteQuality;
+use LeDron_James::topology::Topology;
+
+const PRIOR: f64 = 0.1;
+
+fn topology() -> Topology {
+    let mut topology = Topology::new();
+    for path in [vec![10, 1, 20], vec![10, 2, 3, 20]] {
+        let trace: Vec<(NodeId, NodeType)> = (path.iter())
+            .map(|id| match id {
+                10 => (*id, NodeType::Client),
+                20 => (*id, NodeType::Server),
+                _ => (*id, NodeType::Drone),
+            })
+            .collect();
+        topology.ingest_path(&trace);
+    }
+    topology
+}
+
+fn quality() -> RouteQuality {
+    RouteQuality::new(PRIOR, Duration::from_secs(600))
+}
+
+// What client 10 receives back, `hops` going from whoever sent it to 10.
+fn received(hops: &[NodeId], pack_type: PacketType) -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader {
+            hop_index: hops.len() - 1,
+            hops: hops.to_vec(),
+        },
+        session_id: 1,
+        pack_type,
+    }
+}
+
+fn nack(hops: &[NodeId], nack_type: NackType) -> Packet {
+    received(
+        hops,
+        PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type,
+        }),
+    )
+}
+
+fn ack(hops: &[NodeId]) -> Packet {
+    received(hops, PacketType::Ack(Ack { fragment_index: 0 }))
+}
+
+#[test]
+fn unknown_drones_have_the_prior() {
+    let quality = quality();
+    assert_eq!(quality.pdr(1), PRIOR);
+    let expected = (1.0 - PRIOR) * (1.0 - PRIOR);
+    assert!((quality.delivery_probability(&[10, 2, 3, 20]) - expected).abs() < 1e-12);
+    assert_eq!(quality.delivery_probability(&[10, 20]), 1.0);
+    // Out of range priors are clamped
+    assert_eq!(RouteQuality::new(2.0, Duration::from_secs(1)).pdr(1), 0.99);
+    assert_eq!(RouteQuality::new(-1.0, Duration::from_secs(1)).pdr(1), 0.0);
+}
+
+#[test]
+fn dropped_nacks_blame_the_reporter_and_clear_the_drones_before_it() {
+    let mut quality = quality();
+    quality.record(&nack(&[3, 2, 10], NackType::Dropped));
+    assert!(quality.pdr(3) > PRIOR);
+    assert!(quality.pdr(2) < PRIOR);
+    assert_eq!(quality.pdr(10), PRIOR); // Neither the client
+    assert_eq!(quality.pdr(1), PRIOR); // nor drones off the path are touched
+}
+
+#[test]
+fn error_in_routing_blames_the_unreachable_node() {
+    let mut quality = quality();
+    quality.record(&nack(&[2, 10], NackType::ErrorInRouting(3)));
+    assert!(quality.pdr(3) > PRIOR);
+    assert_eq!(quality.pdr(2), PRIOR); // The reporter itself did its job
+}
+
+#[test]
+fn other_nacks_only_clear_the_drones_before_the_reporter() {
+    let mut quality = quality();
+    quality.record(&nack(&[3, 2, 10], NackType::UnexpectedRecipient(3)));
+    quality.record(&nack(&[3, 2, 10], NackType::DestinationIsDrone));
+    assert_eq!(quality.pdr(3), PRIOR);
+    assert!(quality.pdr(2) < PRIOR);
+}
+
+#[test]
+fn acks_clear_every_drone_of_the_path() {
+    let mut quality = quality();
+    quality.record(&ack(&[20, 3, 2, 10]));
+    assert!(quality.pdr(2) < PRIOR);
+    assert!(quality.pdr(3) < PRIOR);
+    assert_eq!(quality.pdr(20), PRIOR);
+    let before = quality.pdr(2);
+    quality.record(&ack(&[20, 3, 2, 10]));
+    assert!(quality.pdr(2) < before);
+    // Fragments and floods tell nothing
+    let fragment = PacketType::MsgFragment(fragment_message(b"hi").remove(0));
+    quality.record(&received(&[20, 1, 10], fragment));
+    assert_eq!(quality.pdr(1), PRIOR);
+}
+
+#[test]
+fn estimates_stay_below_one() {
+    let mut quality = quality();
+    for _ in 0..1000 {
+        quality.record(&nack(&[1, 10], NackType::Dropped));
+    }
+    assert!(quality.pdr(1) <= 0.99);
+    assert!(quality.delivery_probability(&[10, 1, 20]) > 0.0);
+}
+
+#[test]
+fn old_observations_decay_back_to_the_prior() {
+    let mut quality = RouteQuality::new(PRIOR, Duration::from_millis(10));
+    for _ in 0..20 {
+        quality.record(&nack(&[1, 10], NackType::Dropped));
+    }
+    assert!(quality.pdr(1) > 0.5);
+    thread::sleep(Duration::from_millis(200));
+    assert!((quality.pdr(1) - PRIOR).abs() < 0.01);
+}
+
+#[test]
+fn best_path_is_the_shortest_between_equally_reliable_drones() {
+    let quality = quality();
+    assert_eq!(quality.best_path(&topology(), 10, 20).unwrap(), [10, 1, 20]);
+    assert_eq!(quality.best_path(&topology(), 10, 99), None);
+    let route = quality.best_route(&topology(), 10, 20).unwrap();
+    assert_eq!(route.hop_index, 1);
+    assert_eq!(route.hops, [10, 1, 20]);
+}
+
+#[test]
+fn nacks_move_the_best_path_away_from_a_lossy_drone() {
+    let mut quality = quality();
+    for _ in 0..5 {
+        quality.record(&nack(&[1, 10], NackType::Dropped));
+        quality.record(&ack(&[20, 3, 2, 10]));
+    }
+    assert!(
+        quality.delivery_probability(&[10, 2, 3, 20]) > quality.delivery_probability(&[10, 1, 20])
+    );
+    assert_eq!(
+        quality.best_path(&topology(), 10, 20).unwrap(),
+        [10, 2, 3, 20]
+    );
+    // Once drone 1 delivers again it's preferred back
+    for _ in 0..200 {
+        quality.record(&ack(&[20, 1, 10]));
+    }
+    assert_eq!(quality.best_path(&topology(), 10, 20).unwrap(), [10, 1, 20]);
+}
@@ -0,0 +1,101 @@
+// Route validation on drone 1 between 0 and 2: what gets refused and how it's nacked.
+
+mod common;
+
+use common::*;
+use wg_2024::controller::DroneEvent;
+use wg_2024::drone::Drone as _;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+
+// Runs drone 1 on `packet`, returns the Nack it sent back to 0 and its invalid route count.
+fn refused(packet: Packet) -> (Packet, Outcome, u64) {
+    let (mut drone, ends) = drone_between_0_and_2().queue([packet]);
+    drone.run();
+    let mut outcome = ends.outcome();
+    let invalid_routes = drone.counters().invalid_routes;
+    let (0, nack) = outcome.sent.remove(0) else {
+        panic!("{:?}", outcome);
+    };
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    (nack, outcome, invalid_routes)
+}
+
+fn nack_type(packet: &Packet) -> NackType {
+    match &packet.pack_type {
+        PacketType::Nack(nack) => nack.nack_type.clone(),
+        _ => panic!("not a Nack: {:?}", packet),
+    }
+}
+
+// Only the Nack is reported, as sent: a refused route isn't a PDR drop.
+fn only_nack_reported(outcome: &Outcome) -> bool {
+    matches!(outcome.events.as_slice(), [DroneEvent::PacketSent(p)] if is_nack(p))
+}
+
+#[test]
+fn revisiting_routes_are_refused_as_unexpected_recipient() {
+    // Through us twice, or through someone else twice
+    for (hops, hop_index, back) in [
+        (vec![0, 1, 2, 1, 3], 1, vec![1, 0]),
+        (vec![5, 0, 1, 2, 5], 2, vec![1, 0, 5]),
+    ] {
+        let (nack, outcome, invalid_routes) = refused(packet(hops, hop_index, fragment(2, 3)));
+        assert_eq!(nack_type(&nack), NackType::UnexpectedRecipient(1));
+        assert_eq!(
+            nack.routing_header,
+            SourceRoutingHeader {
+                hop_index: 1,
+                hops: back,
+            }
+        );
+        assert!(only_nack_reported(&outcome), "{:?}", outcome);
+        assert_eq!(invalid_routes, 1);
+    }
+}
+
+#[test]
+fn routes_bouncing_back_are_refused_as_unexpected_recipient() {
+    let (nack, outcome, invalid_routes) = refused(packet(vec![0, 1, 0, 3], 1, fragment(0, 1)));
+    assert_eq!(nack_type(&nack), NackType::UnexpectedRecipient(1));
+    assert_eq!(nack.routing_header.hops, [1, 0]);
+    assert!(only_nack_reported(&outcome), "{:?}", outcome);
+    assert_eq!(invalid_routes, 1);
+}
+
+#[test]
+fn next_hops_we_are_not_linked_to_are_errors_in_routing() {
+    let (nack, outcome, invalid_routes) = refused(packet(vec![0, 1, 7, 9], 1, fragment(0, 1)));
+    assert_eq!(nack_type(&nack), NackType::ErrorInRouting(7));
+    assert_eq!(nack.routing_header.hops, [1, 0]);
+    assert!(only_nack_reported(&outcome), "{:?}", outcome);
+    assert_eq!(invalid_routes, 1);
+}
+
+#[test]
+fn refused_acks_and_nacks_go_to_the_controller() {
+    let ack = PacketType::Ack(Ack { fragment_index: 0 });
+    let outcome = drone_between_0_and_2().run([
+        packet(vec![0, 1, 0, 3], 1, ack.clone()),
+        packet(vec![0, 1, 2, 1, 3], 1, ack),
+    ]);
+    assert!(outcome.sent.is_empty(), "{:?}", outcome);
+    assert_eq!(outcome.events.len(), 2);
+    assert!(outcome
+        .events
+        .iter()
+        .all(|e| matches!(e, DroneEvent::ControllerShortcut(_))));
+}
+
+#[test]
+fn flood_responses_may_come_back_through_a_drone() {
+    let response = PacketType::FloodResponse(FloodResponse {
+        flood_id: 1,
+        path_trace: vec![],
+    });
+    let outcome = drone_between_0_and_2().run([packet(vec![0, 1, 2, 0], 1, response)]);
+    let [(2, forwarded)] = outcome.sent.as_slice() else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(forwarded.routing_header.hop_index, 2);
+}
@@ -0,0 +1,160 @@
+// Scenario parsing, and expectations evaluated against client 10 - drones 1, 2 - server 20.
+
+mod common;
+
+use common::line;
+use std::time::Duration;
+use LeDron_James::network::Network;
+use LeDron_James::scenario::*;
+
+const GRACE: Duration = Duration::from_millis(100);
+
+fn network() -> Network {
+    Network::spawn(line(10, &[1, 2], 20, 0.0), false)
+}
+
+#[test]
+fn parses_actions_in_time_order() {
+    let scenario = Scenario::parse(
+        "# Comment line
+        t=500ms set pdr of 5 to 0.4
+        t=0 flood from client 10 ; t=1.5s flood from 11   # Two floods
+        t=200ms crash drone 3; t=800ms recover drone 3
+
+        t=0 send 4 fragments of session 7 along 10 1 3 20",
+    )
+    .unwrap();
+    let actions: Vec<String> = (scenario.actions.iter())
+        .map(|(at, action)| format!("{:?} {:?}", at, action))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            "0ns Flood { from: 10, flood_id: 1 }",
+            "0ns Send { path: [10, 1, 3, 20], count: 4, session_id: 7 }",
+            "200ms Crash(3)",
+            "500ms SetPdr(5, 0.4)",
+            "800ms Recover(3)",
+            "1.5s Flood { from: 11, flood_id: 2 }",
+        ]
+    );
+    assert!(scenario.expectations.is_empty());
+}
+
+#[test]
+fn parses_expectations() {
+    let scenario = Scenario::parse(
+        "expect all fragments of session 7 acked by t=2s
+        expect all fragments of session 7 delivered by t=150ms
+        expect nack for session 8 by t=1s
+        expect no nack for session 9",
+    )
+    .unwrap();
+    let expectations: Vec<String> = (scenario.expectations.iter())
+        .map(|e| format!("{:?}", e))
+        .collect();
+    assert_eq!(
+        expectations,
+        [
+            "Acked { session_id: 7, by: 2s }",
+            "Delivered { session_id: 7, by: 150ms }",
+            "Nacked { session_id: 8, by: 1s }",
+            "NoNack { session_id: 9 }",
+        ]
+    );
+}
+
+#[test]
+fn malformed_lines_are_refused() {
+    for statement in [
+        "crash drone 3",                                   // No time
+        "t=1h crash drone 3",                              // Unknown unit
+        "t=-1s crash drone 3",                             // Negative time
+        "t=0 crash drone three",                           // Not an id
+        "t=0 crash drone 300",                             // Id out of range
+        "t=0 explode drone 3",                             // Unknown action
+        "t=0 flood from",                                  // Missing id
+        "t=0 set pdr of 5 to high",                        // Not a pdr
+        "t=0 send x fragments of session 7 along 10 20",   // Not a count
+        "t=0 send 4 fragments of session 7 along 10 x",    // Not a path
+        "expect all fragments of session 7 acked by 2s",   // Deadline without t=
+        "expect all fragments of session x acked by t=2s", // Not a session
+        "expect nack for session 7",                       // Missing deadline
+        "expect everything",
+    ] {
+        let text = format!("t=0 flood from 10\n{}", statement);
+        match Scenario::parse(&text) {
+            Err(er) => assert_eq!(er, format!("Can't parse '{}'", statement)),
+            Ok(scenario) => panic!("'{}' parsed as {:?}", statement, scenario),
+        }
+    }
+}
+
+#[test]
+fn expectations_hold_on_a_lossless_line() {
+    let scenario = Scenario::parse(
+        "t=0 send 3 fragments of session 7 along 10 1 2 20
+        expect all fragments of session 7 delivered by t=1s
+        expect all fragments of session 7 acked by t=1s
+        expect no nack for session 7",
+    )
+    .unwrap();
+    let report = scenario.run(&mut network(), GRACE);
+    assert!(report.success(), "{:?}", report.failures);
+    assert_eq!(report.passed.len(), 3);
+}
+
+#[test]
+fn expectations_fail_when_a_drone_drops_everything() {
+    let scenario = Scenario::parse(
+        "t=0 set pdr of 2 to 1
+        t=50ms send 3 fragments of session 7 along 10 1 2 20
+        expect nack for session 7 by t=1s
+        expect all fragments of session 7 delivered by t=1s
+        expect all fragments of session 7 acked by t=1s
+        expect no nack for session 7",
+    )
+    .unwrap();
+    let report = scenario.run(&mut network(), GRACE);
+    assert!(!report.success());
+    assert_eq!(report.passed, ["Nacked { session_id: 7, by: 1s }"]);
+    assert_eq!(report.failures.len(), 3);
+    assert!(
+        report.failures[0].ends_with("0/3 delivered"),
+        "{:?}",
+        report.failures
+    );
+    assert!(
+        report.failures[1].ends_with("0/3 acked"),
+        "{:?}",
+        report.failures
+    );
+    assert!(
+        report.failures[2].contains("first nack at"),
+        "{:?}",
+        report.failures
+    );
+}
+
+#[test]
+fn failing_actions_and_unknown_sessions_are_reported() {
+    let scenario = Scenario::parse(
+        "t=0 crash drone 99
+        expect all fragments of session 5 delivered by t=50ms
+        expect no nack for session 5",
+    )
+    .unwrap();
+    let report = scenario.run(&mut network(), GRACE);
+    assert_eq!(report.passed, ["NoNack { session_id: 5 }"]);
+    assert_eq!(report.failures.len(), 2);
+    assert!(
+        report.failures[0].starts_with("t=0ns Crash(99)"),
+        "{:?}",
+        report.failures
+    );
+    assert!(
+        report.failures[1].ends_with("session never seen"),
+        "{:?}",
+        report.failures
+    );
+}
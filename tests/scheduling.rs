@@ -0,0 +1,98 @@
+// Scheduling of drone 1 between 0 and 2: fragments, Acks and floods all going to 2.
+
+mod common;
+
+use common::*;
+use std::time::Duration;
+use wg_2024::controller::DroneCommand;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+
+fn scheduled(scheduling: Scheduling, queue_size: Option<usize>) -> Fixture {
+    let config = DroneConfig {
+        scheduling,
+        queue_size,
+        ..quiet()
+    };
+    drone_with(1, &[0, 2], config, 0.0)
+}
+
+fn to_2(fragment_index: u64) -> Packet {
+    packet(vec![0, 1, 2], 1, fragment(fragment_index, 100))
+}
+
+fn ack_to_2(fragment_index: u64) -> Packet {
+    packet(vec![0, 1, 2], 1, PacketType::Ack(Ack { fragment_index }))
+}
+
+// What 2 got, in order: F(index) for fragments, A(index) for Acks, R for FloodRequests.
+fn order(fixture: Fixture, packets: Vec<Packet>) -> Vec<String> {
+    let outcome = fixture.run(packets);
+    (outcome.sent.into_iter())
+        .map(|(to, packet)| {
+            assert_eq!(to, 2, "{:?}", packet);
+            match packet.pack_type {
+                PacketType::MsgFragment(f) => format!("F{}", f.fragment_index),
+                PacketType::Ack(a) => format!("A{}", a.fragment_index),
+                PacketType::FloodRequest(_) => "R".to_string(),
+                _ => panic!("{:?}", packet),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn priority_puts_control_packets_and_floods_ahead_of_fragments() {
+    let packets = vec![
+        to_2(0),
+        to_2(1),
+        flood_request(1, 10, &[10, 0]),
+        to_2(2),
+        ack_to_2(0),
+    ];
+    let sent = order(scheduled(Scheduling::Priority, None), packets.clone());
+    assert_eq!(sent, ["A0", "R", "F0", "F1", "F2"]);
+    // Fifo keeps the arrival order
+    let sent = order(scheduled(Scheduling::Fifo, None), packets);
+    assert_eq!(sent, ["F0", "F1", "R", "F2", "A0"]);
+}
+
+#[test]
+fn weighted_classes_get_their_share() {
+    let mut packets: Vec<Packet> = (0..6).map(to_2).collect();
+    packets.extend((0..3).map(ack_to_2));
+    let weights = Scheduling::Weighted {
+        control: 1,
+        flood: 1,
+        fragment: 3,
+    };
+    let sent = order(scheduled(weights, None), packets);
+    assert_eq!(sent, ["A0", "F0", "F1", "F2", "A1", "F3", "F4", "F5", "A2"]);
+}
+
+#[test]
+fn the_queue_takes_at_most_queue_size_packets() {
+    let mut packets: Vec<Packet> = (0..8).map(to_2).collect();
+    packets.push(ack_to_2(0));
+    // The Ack waits in the channel until there's room for it
+    let sent = order(scheduled(Scheduling::Priority, Some(4)), packets);
+    assert_eq!(sent, ["F0", "F1", "F2", "F3", "F4", "A0", "F5", "F6", "F7"]);
+}
+
+#[test]
+fn a_crash_gets_through_a_backlog() {
+    let fixture = scheduled(Scheduling::Priority, None);
+    for index in 0..100_000 {
+        fixture.packet_send.send(to_2(index)).unwrap();
+    }
+    let running = fixture.spawn();
+    running.ends.commands.send(DroneCommand::Crash).unwrap();
+    // Crashed drones nack fragments back to 0 long before the backlog is over
+    let (_, to_0) = &running.ends.neighbours[0];
+    let nack = to_0.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(!running.packet_send.is_empty());
+    let PacketType::Nack(nack) = nack.pack_type else {
+        panic!("{:?}", nack);
+    };
+    assert_eq!(nack.nack_type, NackType::ErrorInRouting(1));
+}
@@ -0,0 +1,205 @@
+// Server 20 reached by client 10 through drones 1, 2 and 3, its only neighbour is drone 3.
+
+use crossbeam_channel::unbounded;
+use std::collections::HashMap;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::fragment::*;
+use LeDron_James::server::*;
+
+const REQUEST: u64 = 7;
+const REPLY: u64 = (20 << 56) | 1; // First session opened by server 20
+
+fn route_to_server() -> SourceRoutingHeader {
+    SourceRoutingHeader {
+        hop_index: 4,
+        hops: vec![10, 1, 2, 3, 20],
+    }
+}
+
+fn route_to_client() -> SourceRoutingHeader {
+    SourceRoutingHeader {
+        hop_index: 1,
+        hops: vec![20, 3, 2, 1, 10],
+    }
+}
+
+fn packet(session_id: u64, routing_header: SourceRoutingHeader, pack_type: PacketType) -> Packet {
+    Packet {
+        routing_header,
+        session_id,
+        pack_type,
+    }
+}
+
+fn request(data: &[u8]) -> Vec<Packet> {
+    (fragment_message(data).into_iter())
+        .map(|f| packet(REQUEST, route_to_server(), PacketType::MsgFragment(f)))
+        .collect()
+}
+
+fn to_server(session_id: u64, pack_type: PacketType) -> Packet {
+    packet(session_id, route_to_server(), pack_type)
+}
+
+fn nack(session_id: u64, fragment_index: u64, nack_type: NackType) -> Packet {
+    to_server(
+        session_id,
+        PacketType::Nack(Nack {
+            fragment_index,
+            nack_type,
+        }),
+    )
+}
+
+fn ack(session_id: u64, fragment_index: u64) -> Packet {
+    to_server(session_id, PacketType::Ack(Ack { fragment_index }))
+}
+
+// Runs the server on `packets` and returns what it sent to drone 3.
+fn run<H: MessageHandler>(handler: H, packets: Vec<Packet>) -> Vec<Packet> {
+    let (packet_send, packet_recv) = unbounded();
+    let (drone_send, drone_recv) = unbounded();
+    let mut server = Server::new(20, packet_recv, HashMap::from([(3, drone_send)]), handler);
+    server.logging_enabled(false);
+    for packet in packets {
+        packet_send.send(packet).unwrap();
+    }
+    drop(packet_send);
+    server.run();
+    drone_recv.try_iter().collect()
+}
+
+fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 3) as u8).collect()
+}
+
+fn acked(packets: &[Packet]) -> Vec<(u64, u64)> {
+    (packets.iter())
+        .filter_map(|p| match &p.pack_type {
+            PacketType::Ack(ack) => Some((p.session_id, ack.fragment_index)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn fragments(packets: &[Packet]) -> Vec<(u64, Fragment)> {
+    (packets.iter())
+        .filter_map(|p| match &p.pack_type {
+            PacketType::MsgFragment(f) => Some((p.session_id, f.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn acks_every_fragment_along_the_reversed_route() {
+    let mut packets = request(&message(300));
+    packets.swap(0, 2);
+    let sent = run(EchoHandler, packets);
+    assert_eq!(acked(&sent), [(REQUEST, 2), (REQUEST, 1), (REQUEST, 0)]);
+    assert!(sent.iter().all(|p| p.routing_header == route_to_client()));
+}
+
+#[test]
+fn complete_messages_are_echoed_back() {
+    let data = message(300);
+    let sent = run(EchoHandler, request(&data));
+    let reply = fragments(&sent);
+    assert_eq!(reply.len(), 3);
+    assert!(reply.iter().all(|(session_id, _)| *session_id == REPLY));
+    let mut reassembler = Reassembler::new();
+    let echo = (reply.iter()).find_map(|(_, f)| reassembler.insert(REPLY, f));
+    assert_eq!(echo, Some(data));
+}
+
+struct Silent(Vec<(NodeId, u64, Vec<u8>)>);
+impl MessageHandler for &mut Silent {
+    fn handle_message(
+        &mut self,
+        source: NodeId,
+        session_id: u64,
+        data: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        self.0.push((source, session_id, data));
+        None
+    }
+}
+
+#[test]
+fn handlers_without_a_reply_only_get_acks_sent() {
+    let data = message(10);
+    let mut handler = Silent(vec![]);
+    let sent = run(&mut handler, request(&data));
+    assert_eq!(handler.0, [(10, REQUEST, data)]);
+    assert_eq!(acked(&sent), [(REQUEST, 0)]);
+    assert!(fragments(&sent).is_empty());
+}
+
+#[test]
+fn dropped_fragments_of_a_reply_are_retransmitted_until_acked() {
+    let mut packets = request(&message(300));
+    packets.push(nack(REPLY, 1, NackType::Dropped));
+    packets.push(ack(REPLY, 0));
+    packets.push(ack(REPLY, 1));
+    packets.push(nack(REPLY, 2, NackType::Dropped));
+    packets.push(ack(REPLY, 2));
+    packets.push(nack(REPLY, 2, NackType::Dropped)); // The reply is forgotten by now
+    let sent = run(EchoHandler, packets);
+    let indexes: Vec<u64> = (fragments(&sent).iter())
+        .map(|(_, f)| f.fragment_index)
+        .collect();
+    assert_eq!(indexes, [0, 1, 2, 1, 2]);
+    assert!(sent.iter().all(|p| p.routing_header == route_to_client()));
+}
+
+#[test]
+fn other_nacks_are_not_recoverable() {
+    let mut packets = request(&message(10));
+    packets.push(nack(REPLY, 0, NackType::ErrorInRouting(2)));
+    packets.push(nack(REPLY, 0, NackType::UnexpectedRecipient(2)));
+    packets.push(nack(REPLY, 0, NackType::DestinationIsDrone));
+    packets.push(nack(REPLY, 5, NackType::Dropped)); // No such fragment
+    packets.push(nack(REPLY + 1, 0, NackType::Dropped)); // No such session
+    let sent = run(EchoHandler, packets);
+    assert_eq!(fragments(&sent).len(), 1); // Only the reply itself
+}
+
+#[test]
+fn flood_requests_are_answered_as_a_server() {
+    let path_trace = vec![
+        (10, NodeType::Client),
+        (1, NodeType::Drone),
+        (3, NodeType::Drone),
+    ];
+    let flood = packet(
+        0,
+        SourceRoutingHeader {
+            hop_index: 0,
+            hops: vec![],
+        },
+        PacketType::FloodRequest(FloodRequest {
+            flood_id: 4,
+            initiator_id: 10,
+            path_trace: path_trace.clone(),
+        }),
+    );
+    let sent = run(EchoHandler, vec![flood]);
+    let [response] = sent.as_slice() else {
+        panic!("{:?}", sent);
+    };
+    assert_eq!(
+        response.routing_header,
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![20, 3, 1, 10],
+        }
+    );
+    let PacketType::FloodResponse(response) = &response.pack_type else {
+        panic!("{:?}", response);
+    };
+    assert_eq!(response.flood_id, 4);
+    let mut expected = path_trace;
+    expected.push((20, NodeType::Server));
+    assert_eq!(response.path_trace, expected);
+}
@@ -0,0 +1,158 @@
+// Drone snapshots and Network checkpoints: what a restored drone or network carries on with.
+
+mod common;
+
+use common::*;
+use crossbeam_channel::unbounded;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use wg_2024::drone::Drone as _;
+use wg_2024::packet::*;
+use LeDron_James::builder::*;
+use LeDron_James::network::{Network, NetworkEvent};
+use LeDron_James::snapshot::{DroneSnapshot, NetworkCheckpoint};
+use LeDron_James::Drone;
+
+const IDLE: Duration = Duration::from_millis(100);
+
+fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("ledron-{}-{}.json", name, std::process::id()));
+    path.to_str().unwrap().to_string()
+}
+
+fn seeded() -> Fixture {
+    let config = DroneConfig {
+        seed: Some(42),
+        ..quiet()
+    };
+    drone_with(1, &[0, 2], config, 0.5)
+}
+
+fn fragments(indexes: std::ops::Range<u64>) -> Vec<Packet> {
+    indexes
+        .map(|i| packet(vec![0, 1, 2], 1, fragment(i, 100)))
+        .collect()
+}
+
+// Indexes of the fragments that got through to 2.
+fn forwarded(ends: &Ends) -> BTreeSet<u64> {
+    (ends.outcome().sent.into_iter())
+        .filter_map(|(to, p)| match p.pack_type {
+            PacketType::MsgFragment(f) if to == 2 => Some(f.fragment_index),
+            _ => None,
+        })
+        .collect()
+}
+
+// `snapshot` wired to fresh channels, like Network::restore does.
+fn restored(snapshot: DroneSnapshot) -> (Drone, Ends) {
+    let (controller_send, events) = unbounded();
+    let (commands, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let mut builder = DroneBuilder::from_snapshot(snapshot)
+        .controller(controller_send, command_recv)
+        .packet_recv(packet_recv);
+    let mut neighbours = Vec::new();
+    for id in [0, 2] {
+        let (tx, rx) = unbounded();
+        builder = builder.neighbour(id, tx);
+        neighbours.push((id, rx));
+    }
+    for packet in fragments(50..100) {
+        packet_send.send(packet).unwrap();
+    }
+    let ends = Ends {
+        neighbours,
+        events,
+        commands,
+    };
+    (builder.build().unwrap(), ends)
+}
+
+#[test]
+fn a_restored_drone_continues_the_drop_sequence() {
+    // The whole sequence in one go
+    let (mut reference, ends) = seeded().queue(fragments(0..100));
+    reference.run();
+    let expected = forwarded(&ends);
+    assert!(expected.len() > 20 && expected.len() < 80, "{:?}", expected);
+
+    // Half of it, then the other half on a drone restored from a saved snapshot
+    let (mut first, first_ends) = seeded().queue(fragments(0..50));
+    first.run();
+    let path = temp_path("drone-snapshot");
+    first.snapshot().save(&path).unwrap();
+    let snapshot = DroneSnapshot::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(snapshot.state.counters.forwarded, 50); // Fragments and Nacks
+    let (mut second, second_ends) = restored(snapshot);
+    second.run();
+
+    let mut got = forwarded(&first_ends);
+    got.extend(forwarded(&second_ends));
+    assert_eq!(got, expected);
+    // Reseeding would have started the sequence over
+    let second_half: BTreeSet<u64> = expected.range(50..).map(|i| i - 50).collect();
+    let first_half: BTreeSet<u64> = expected.range(..50).copied().collect();
+    assert_ne!(first_half, second_half);
+}
+
+#[test]
+fn restored_drones_keep_their_state() {
+    let (mut drone, _ends) = seeded().crash().queue([flood_request(1, 10, &[10, 0])]);
+    drone.run();
+    let (restored, _ends) = restored(drone.snapshot());
+    let (before, after) = (drone.state(), restored.state());
+    assert!(after.crashed);
+    assert_eq!(after.pdr, 0.5);
+    assert_eq!(after.config, before.config);
+    assert_eq!(after.flood_history, before.flood_history);
+    assert_eq!(after.counters.forwarded, before.counters.forwarded);
+}
+
+fn received(events: &[NetworkEvent]) -> Vec<(u8, bool)> {
+    (events.iter())
+        .filter_map(|event| match event {
+            NetworkEvent::Received(id, packet) => {
+                Some((*id, matches!(packet.pack_type, PacketType::MsgFragment(_))))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn a_network_survives_a_checkpoint_saved_to_disk() {
+    let mut network = Network::spawn(line(10, &[1, 2, 3], 20, 0.0), false);
+    network.flood(10, 1).unwrap();
+    network.send_fragments(vec![10, 1, 2, 3, 20], 2, 7).unwrap();
+    network.settle(IDLE);
+    network.crash(3).unwrap();
+    network.settle(IDLE);
+    let before = network.dump_state(2).unwrap();
+    let delivered = network.stats().delivered;
+
+    let path = temp_path("network-checkpoint");
+    network.checkpoint().unwrap().save(&path).unwrap();
+    network.shutdown();
+    let checkpoint = NetworkCheckpoint::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let mut network = Network::restore(checkpoint).unwrap();
+
+    assert!(network.is_crashed(3));
+    assert!(network.neighbours_of(3).is_empty());
+    assert_eq!(network.neighbours_of(2), [1]);
+    assert_eq!(network.stats().delivered, delivered);
+    let after = network.dump_state(2).unwrap();
+    assert_eq!(after.flood_history, before.flood_history);
+    assert!(after.flood_history.contains_key(&10));
+    assert_eq!(after.counters.forwarded, before.counters.forwarded);
+    assert_eq!(after.neighbours, [1]);
+
+    network.recover(3).unwrap();
+    assert_eq!(network.neighbours_of(2), [1, 3]);
+    network.send_fragments(vec![10, 1, 2, 3, 20], 1, 8).unwrap();
+    let events = network.settle(IDLE);
+    assert_eq!(received(&events), [(20, true), (10, false)]);
+    network.shutdown();
+}
@@ -0,0 +1,86 @@
+// DumpState of drone 1 between 0 and 2, directly and through a Network.
+
+mod common;
+
+use common::*;
+use std::time::Duration;
+use wg_2024::drone::Drone as _;
+use LeDron_James::control::{self, ControlCommand};
+use LeDron_James::network::Network;
+use LeDron_James::status::{DroneState, FloodRecord};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+fn running_state() -> DroneState {
+    let mut fixture = drone_between_0_and_2();
+    let control = fixture.drone.control_channel();
+    let running = fixture.spawn();
+    running.handle(flood_request(1, 10, &[10, 0]));
+    running.handle(flood_request(2, 10, &[10, 2]));
+    running.handle(flood_request(1, 11, &[11, 0]));
+    running.handle(packet(vec![0, 1, 2], 1, fragment(0, 1)));
+    control::dump_state(&control, TIMEOUT).unwrap()
+}
+
+fn record(flood_id: u64) -> FloodRecord {
+    FloodRecord {
+        flood_id,
+        session_id: SESSION,
+        arrivals: 1,
+    }
+}
+
+#[test]
+fn a_running_drone_dumps_what_it_knows() {
+    let state = running_state();
+    assert_eq!(state.id, 1);
+    assert_eq!(state.neighbours, [0, 2]);
+    assert!(!state.crashed);
+    let initiators: Vec<u8> = state.flood_history.keys().copied().collect();
+    assert_eq!(initiators, [10, 11]);
+    assert_eq!(state.flood_history[&10], [record(1), record(2)]);
+    assert_eq!(state.flood_history[&11], [record(1)]);
+    assert_eq!(state.counters.forwarded, 4); // Three floods and the fragment
+    assert_eq!(state.queue_depth, 0);
+}
+
+#[test]
+fn queue_depth_counts_the_packets_waiting() {
+    let mut fixture = drone_between_0_and_2();
+    let control = fixture.drone.control_channel();
+    // Handled before the packets, which are all still in the channel
+    let (reply, state) = crossbeam_channel::bounded(1);
+    control.send(ControlCommand::DumpState(reply)).unwrap();
+    let fragments = (0..3).map(|i| packet(vec![0, 1, 2], 1, fragment(i, 3)));
+    let (mut drone, _ends) = fixture.queue(fragments);
+    drone.run();
+    assert_eq!(state.try_recv().unwrap().queue_depth, 3);
+}
+
+#[test]
+fn drone_state_goes_to_json_and_back() {
+    let state = running_state();
+    let json = serde_json::to_string(&state).unwrap();
+    let back: DroneState = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.flood_history, state.flood_history);
+    assert_eq!(back.config, state.config);
+    assert_eq!(serde_json::to_string(&back).unwrap(), json);
+}
+
+#[test]
+fn the_network_dumps_every_drone() {
+    let mut network = Network::spawn(line(10, &[1, 2, 3], 20, 0.0), false);
+    network.flood(10, 1).unwrap();
+    network.settle(Duration::from_millis(100));
+    let state = network.dump_state(2).unwrap();
+    assert_eq!(state.neighbours, [1, 3]);
+    assert_eq!(state.flood_history[&10].len(), 1);
+    assert!(network.dump_state(10).is_err()); // A client
+    network.crash(3).unwrap();
+    let all = network.dump_all();
+    let ids: Vec<u8> = all.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, [1, 2, 3]);
+    for (id, state) in &all[..2] {
+        assert_eq!(state.as_ref().unwrap().id, *id);
+    }
+}
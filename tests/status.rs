@@ -0,0 +1,43 @@
+mod common;
+
+use common::*;
+use std::thread;
+use std::time::Duration;
+use wg_2024::controller::DroneCommand;
+use LeDron_James::status::StatusBoard;
+
+#[test]
+fn status_catches_up_shortly_after_a_burst_of_packets() {
+    let mut fixture = drone_between_0_and_2();
+    let board = StatusBoard::new();
+    fixture.drone.status_board(board.clone());
+    assert_eq!(board.get(1).unwrap().counters.forwarded, 0);
+    let running = fixture.spawn();
+    for i in 0..500 {
+        (running.packet_send)
+            .send(packet(vec![0, 1, 2], 1, fragment(i, 500)))
+            .unwrap();
+    }
+    thread::sleep(Duration::from_millis(200));
+    let status = board.get(1).unwrap();
+    assert_eq!(status.counters.forwarded, 500);
+    assert_eq!(status.queue_depth, 0);
+}
+
+#[test]
+fn commands_are_published_right_away() {
+    let mut fixture = drone_between_0_and_2();
+    let board = StatusBoard::new();
+    fixture.drone.status_board(board.clone());
+    let running = fixture.spawn();
+    (running.ends.commands)
+        .send(DroneCommand::SetPacketDropRate(0.5))
+        .unwrap();
+    (running.ends.commands)
+        .send(DroneCommand::RemoveSender(2))
+        .unwrap();
+    thread::sleep(Duration::from_millis(20));
+    let status = board.get(1).unwrap();
+    assert_eq!(status.pdr, 0.5);
+    assert_eq!(status.neighbours, [0]);
+}
@@ -0,0 +1,153 @@
+// Topology rebuilt from the traces of client 10 and server 20 reaching each other through
+// two branches: 10 - 1 - 2 - 20 and 10 - 1 - 3 - 4 - 20, plus client 11 hanging off drone 3.
+
+use std::thread;
+use std::time::Duration;
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::topology::Topology;
+
+fn trace(ids: &[NodeId]) -> Vec<(NodeId, NodeType)> {
+    (ids.iter())
+        .map(|id| match id {
+            10..=19 => (*id, NodeType::Client),
+            20..=29 => (*id, NodeType::Server),
+            _ => (*id, NodeType::Drone),
+        })
+        .collect()
+}
+
+fn topology() -> Topology {
+    let mut topology = Topology::new();
+    topology.ingest_path(&trace(&[10, 1, 2, 20]));
+    topology.ingest_path(&trace(&[10, 1, 3, 4, 20]));
+    topology.ingest_path(&trace(&[11, 3]));
+    topology
+}
+
+fn neighbours(topology: &Topology, id: NodeId) -> Vec<NodeId> {
+    let mut neighbours: Vec<NodeId> = topology.neighbours(id).collect();
+    neighbours.sort();
+    neighbours
+}
+
+#[test]
+fn traces_become_nodes_and_links() {
+    let topology = topology();
+    let mut nodes: Vec<(NodeId, NodeType)> = (topology.nodes())
+        .map(|(id, node_type)| (id, node_type.clone()))
+        .collect();
+    nodes.sort_by_key(|(id, _)| *id);
+    assert_eq!(nodes, trace(&[1, 2, 3, 4, 10, 11, 20]));
+    let mut edges: Vec<(NodeId, NodeId)> = topology.edges().map(|(a, b, _)| (a, b)).collect();
+    edges.sort();
+    assert_eq!(
+        edges,
+        [(1, 2), (1, 3), (1, 10), (2, 20), (3, 4), (3, 11), (4, 20)]
+    );
+    assert_eq!(neighbours(&topology, 1), [2, 3, 10]);
+    assert!(neighbours(&topology, 99).is_empty());
+    assert!(topology.edge_last_seen(20, 2).is_some());
+    assert!(topology.edge_last_seen(2, 3).is_none());
+}
+
+#[test]
+fn only_flood_responses_are_ingested() {
+    let mut topology = Topology::new();
+    let mut packet = Packet {
+        routing_header: SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![20, 2, 1, 10],
+        },
+        session_id: 0,
+        pack_type: PacketType::FloodRequest(FloodRequest {
+            flood_id: 1,
+            initiator_id: 10,
+            path_trace: trace(&[10, 1, 2]),
+        }),
+    };
+    assert!(!topology.ingest_packet(&packet));
+    assert_eq!(topology.nodes().count(), 0);
+    packet.pack_type = PacketType::FloodResponse(FloodResponse {
+        flood_id: 1,
+        path_trace: trace(&[10, 1, 2, 20]),
+    });
+    assert!(topology.ingest_packet(&packet));
+    assert_eq!(topology.route(10, 20).unwrap().hops, [10, 1, 2, 20]);
+}
+
+#[test]
+fn invalid_input_leaves_no_trace() {
+    let mut topology = Topology::new();
+    topology.ingest_path(&[]);
+    topology.ingest_path(&trace(&[5]));
+    topology.add_edge(5, 5);
+    assert_eq!(topology.edges().count(), 0);
+    assert!(neighbours(&topology, 5).is_empty());
+    // Removing what isn't there is a no-op
+    topology.remove_edge(5, 6);
+    topology.remove_node(7);
+    assert_eq!(topology.node_type(5), Some(&NodeType::Drone));
+    assert_eq!(topology.node_type(7), None);
+}
+
+#[test]
+fn shortest_path_takes_the_fewest_hops_through_drones_only() {
+    let topology = topology();
+    assert_eq!(topology.shortest_path(10, 20).unwrap(), [10, 1, 2, 20]);
+    assert_eq!(topology.shortest_path(11, 20).unwrap(), [11, 3, 4, 20]);
+    assert_eq!(topology.shortest_path(4, 10).unwrap(), [4, 3, 1, 10]);
+    assert_eq!(topology.shortest_path(1, 1).unwrap(), [1]);
+    // Never through a client or a server
+    assert_eq!(topology.shortest_path(2, 4).unwrap(), [2, 1, 3, 4]);
+    let mut no_drones = Topology::new();
+    no_drones.ingest_path(&trace(&[10, 20, 11]));
+    assert_eq!(no_drones.shortest_path(10, 11), None);
+    // Unknown or disconnected nodes have no path
+    assert_eq!(topology.shortest_path(10, 99), None);
+    assert_eq!(topology.shortest_path(99, 10), None);
+}
+
+#[test]
+fn route_is_ready_to_be_sent() {
+    let route = topology().route(10, 20).unwrap();
+    assert_eq!(route.hop_index, 1);
+    assert_eq!(route.hops, [10, 1, 2, 20]);
+    assert_eq!(route.current_hop(), Some(1));
+}
+
+#[test]
+fn removed_links_and_nodes_are_routed_around() {
+    let mut topology = topology();
+    topology.remove_edge(2, 1);
+    assert_eq!(topology.shortest_path(10, 20).unwrap(), [10, 1, 3, 4, 20]);
+    assert_eq!(neighbours(&topology, 2), [20]);
+    topology.remove_node(3);
+    assert_eq!(topology.node_type(3), None);
+    assert_eq!(neighbours(&topology, 1), [10]);
+    assert!(neighbours(&topology, 11).is_empty());
+    assert!(topology.edge_last_seen(3, 4).is_none());
+    assert_eq!(topology.shortest_path(10, 20), None);
+    // A new flood brings the link back
+    topology.ingest_path(&trace(&[20, 2, 1, 10]));
+    assert_eq!(topology.shortest_path(10, 20).unwrap(), [10, 1, 2, 20]);
+}
+
+#[test]
+fn links_not_confirmed_in_time_are_forgotten() {
+    let mut topology = topology();
+    thread::sleep(Duration::from_millis(30));
+    topology.ingest_path(&trace(&[10, 1, 2, 20]));
+    topology.forget_older_than(Duration::from_millis(20));
+    let mut edges: Vec<(NodeId, NodeId)> = topology.edges().map(|(a, b, _)| (a, b)).collect();
+    edges.sort();
+    assert_eq!(edges, [(1, 2), (1, 10), (2, 20)]);
+    assert!(neighbours(&topology, 3).is_empty());
+    // Nodes stay known, only their links go
+    assert!(topology.is_drone(3));
+    topology.forget_older_than(Duration::from_secs(60));
+    assert_eq!(topology.edges().count(), 3);
+    topology.clear();
+    assert_eq!(topology.nodes().count(), 0);
+    assert_eq!(topology.shortest_path(10, 20), None);
+}
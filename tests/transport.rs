@@ -0,0 +1,125 @@
+// UdpTransport over loopback, and the addresses the topology gives out.
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::network::*;
+use wg_2024::packet::*;
+use LeDron_James::codec::*;
+use LeDron_James::config::NetworkConfig;
+use LeDron_James::transport::{DroneChannels, UdpTransport};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn ack() -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![1, 2],
+        },
+        session_id: 42,
+        pack_type: PacketType::Ack(Ack { fragment_index: 3 }),
+    }
+}
+
+fn bind(id: NodeId) -> (UdpTransport, SocketAddr) {
+    let transport = UdpTransport::bind(id, "127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = transport.local_addr().unwrap();
+    (transport, addr)
+}
+
+fn wire(
+    transport: UdpTransport,
+    neighbours: &[(NodeId, SocketAddr)],
+    controller: Option<SocketAddr>,
+) -> DroneChannels {
+    let neighbours: HashMap<NodeId, SocketAddr> = neighbours.iter().copied().collect();
+    transport.wire(neighbours, controller).unwrap()
+}
+
+#[test]
+fn packets_go_from_one_transport_to_the_other() {
+    let (one, one_addr) = bind(1);
+    let (two, two_addr) = bind(2);
+    let one = wire(one, &[(2, two_addr)], None);
+    let two = wire(two, &[(1, one_addr)], None);
+    one.packet_send[&2].send(ack()).unwrap();
+    assert_eq!(two.packet_recv.recv_timeout(TIMEOUT).unwrap(), ack());
+    two.packet_send[&1].send(ack()).unwrap();
+    assert_eq!(one.packet_recv.recv_timeout(TIMEOUT).unwrap(), ack());
+}
+
+#[test]
+fn events_reach_the_controller_and_commands_reach_the_drone() {
+    let controller = UdpSocket::bind("127.0.0.1:0").unwrap();
+    controller.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let (one, one_addr) = bind(1);
+    let one = wire(one, &[], Some(controller.local_addr().unwrap()));
+
+    one.controller_send
+        .send(DroneEvent::PacketSent(ack()))
+        .unwrap();
+    let mut buf = vec![0; 65507];
+    let (len, from) = controller.recv_from(&mut buf).unwrap();
+    assert_eq!(from, one_addr);
+    match decode_frame(&buf[..len]).unwrap() {
+        Frame::Event(1, DroneEvent::PacketSent(packet)) => assert_eq!(packet, ack()),
+        other => panic!("{:?}", other),
+    }
+
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::SetPacketDropRate(0.5))).unwrap();
+    controller.send_to(&bytes, one_addr).unwrap();
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::Crash)).unwrap();
+    controller.send_to(&bytes, one_addr).unwrap();
+    assert!(matches!(
+        one.controller_recv.recv_timeout(TIMEOUT),
+        Ok(DroneCommand::SetPacketDropRate(pdr)) if pdr == 0.5
+    ));
+    assert!(matches!(
+        one.controller_recv.recv_timeout(TIMEOUT),
+        Ok(DroneCommand::Crash)
+    ));
+}
+
+#[test]
+fn added_senders_write_to_the_new_neighbour() {
+    let controller = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let (one, one_addr) = bind(1);
+    let (two, two_addr) = bind(2);
+    let one = wire(one, &[], None);
+    let two = wire(two, &[], None);
+    let bytes = encode_frame(&Frame::Command(RemoteCommand::AddSender(2, two_addr))).unwrap();
+    controller.send_to(&bytes, one_addr).unwrap();
+    let Ok(DroneCommand::AddSender(2, sender)) = one.controller_recv.recv_timeout(TIMEOUT) else {
+        panic!("no AddSender");
+    };
+    sender.send(ack()).unwrap();
+    assert_eq!(two.packet_recv.recv_timeout(TIMEOUT).unwrap(), ack());
+}
+
+#[test]
+fn garbage_is_ignored() {
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let (one, one_addr) = bind(1);
+    let one = wire(one, &[], None);
+    peer.send_to(b"not a frame", one_addr).unwrap();
+    let bytes = encode_frame(&Frame::Packet(ack())).unwrap();
+    peer.send_to(&bytes, one_addr).unwrap();
+    assert_eq!(one.packet_recv.recv_timeout(TIMEOUT).unwrap(), ack());
+}
+
+#[test]
+fn base_ports_without_room_for_every_node_are_refused() {
+    let path = std::env::temp_dir().join(format!("ledron-base-port-{}.json", std::process::id()));
+    for (base_port, fits) in [(47000, true), (65279, true), (65280, false), (65535, false)] {
+        std::fs::write(&path, format!("{{ \"base_port\": {} }}", base_port)).unwrap();
+        let config = NetworkConfig::from_file(path.to_str().unwrap());
+        assert_eq!(config.is_ok(), fits, "{} {:?}", base_port, config);
+        if let Ok(config) = config {
+            assert_eq!(config.address_of(255).port(), base_port + 255);
+            assert_eq!(config.controller_address().port(), base_port + 256);
+        }
+    }
+    let _ = std::fs::remove_file(path);
+}